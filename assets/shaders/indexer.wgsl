@@ -0,0 +1,69 @@
+struct PaletteSwapMaterial {
+    tint: vec4<f32>,
+    palette: u32,
+    slots: u32,
+    key_count: u32,
+    keys: array<vec4<f32>, 16>,
+};
+
+@group(1) @binding(0)
+var<uniform> material: PaletteSwapMaterial;
+@group(1) @binding(1)
+var source_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var source_sampler: sampler;
+@group(1) @binding(3)
+var palette_texture: texture_2d<f32>;
+
+struct FragmentInput {
+    #import bevy_sprite::mesh2d_vertex_output
+};
+
+// Keys are written as sRGB values, but the sheet is sampled as linear.
+fn to_srgb(rgb: vec3<f32>) -> vec3<f32> {
+    let low = rgb * 12.92;
+    let high = 1.055 * pow(rgb, vec3<f32>(1.0 / 2.4)) - 0.055;
+
+    return select(high, low, rgb <= vec3<f32>(0.0031308));
+}
+
+fn is_key(color: vec4<f32>, key: vec4<f32>) -> bool {
+    return color.a > 0.999 && all(abs(to_srgb(color.rgb) - key.rgb) < vec3<f32>(0.5 / 255.0));
+}
+
+// Stretches or squashes the key slots over the palette, like
+// `GreacherColorPalette::ramp`.
+fn ramp_slot(slot: u32) -> u32 {
+    if (material.key_count <= 1u) {
+        return material.slots / 2u;
+    }
+
+    let last = material.slots - 1u;
+    let steps = material.key_count - 1u;
+
+    return min((2u * slot * last + steps) / (2u * steps), last);
+}
+
+// Empty palettes are transparent, like `GreacherColorPalette::ramp` makes
+// them.
+fn palette_color(column: u32) -> vec4<f32> {
+    if (material.slots == 0u) {
+        return vec4<f32>(0.0);
+    }
+
+    return textureLoad(palette_texture, vec2<i32>(i32(column), i32(material.palette)), 0);
+}
+
+@fragment
+fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {
+    var color = textureSample(source_texture, source_sampler, in.uv);
+
+    for (var slot = 0u; slot < material.key_count; slot = slot + 1u) {
+        if (is_key(color, material.keys[slot])) {
+            color = palette_color(ramp_slot(slot));
+            break;
+        }
+    }
+
+    return color * material.tint;
+}
@@ -0,0 +1,430 @@
+//! Generates greachers without a window or GPU, and writes their heads out as
+//! PNGs along with a labelled contact sheet.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use ab_glyph::{point, Font, FontVec, PxScale, ScaleFont};
+use anyhow::{anyhow, bail, Context};
+use bevy::{
+    prelude::Image,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use greachers_survival::{
+    color::GreacherPalettes,
+    greachers::{
+        components::{Greacher, GreacherParts},
+        gen::{
+            create_head_image, find_head_pattern, generate_head_pattern_preview, GeneratorVersion,
+            HeadPattern, GREACHER_CANVAS_SIZE, HEAD_PATTERNS, MIN_CANVAS_SIZE,
+        },
+        genome::Genome,
+        names::NameLanguage,
+        rarity::RarityWeights,
+    },
+    palettes::{PaletteFormat, PaletteSet},
+};
+use image::{imageops, Rgba, RgbaImage};
+
+const USAGE: &str = "\
+Usage: greacher-gen [SEEDS] [OPTIONS]
+
+Seeds (pick one, defaults to --count 16):
+    --seed N            a single seed
+    --seeds FROM..TO    every seed in a range, end exclusive
+    --count N           N random seeds
+    --code CODE         a greacher code, can be given more than once. Needs
+                        the --palettes and --language it was made with
+
+Options:
+    --out DIR           where to write the PNGs [default: greacher-gen]
+    --assets DIR        the game's assets folder [default: assets]
+    --language FILE     a .names.ron file to name greachers with
+    --palettes FILE     a palette image, or a .gpl, .pal, .hex or .palette.ron
+                        palette set [default: ASSETS/palette.png]
+    --canvas N          head canvas size in pixels [default: 10]
+    --scale N           upscaling factor of the exported heads [default: 8]
+    --columns N         heads per row of the contact sheet [default: 8]
+    --atlas             export every head frame instead of just the idle one,
+                        and generated bodies next to them
+    --pattern NAME      draw only the given head pattern, to preview it
+    --generator N       generate seeds the way version N of the generators did
+                        [default: the latest]
+    --reroll PARTS      reroll some parts of every greacher, a comma separated
+                        list of head, body, stats, name and palette
+";
+
+const LABEL_FONT_SIZE: f32 = 8.0;
+const LABEL_LINE_HEIGHT: u32 = 10;
+const MIN_CELL_WIDTH: u32 = 120;
+const CELL_PADDING: u32 = 4;
+const SHEET_BACKGROUND: Rgba<u8> = Rgba([32, 30, 40, 255]);
+const LABEL_COLOR: Rgba<u8> = Rgba([240, 240, 240, 255]);
+const SEED_COLOR: Rgba<u8> = Rgba([150, 150, 160, 255]);
+
+struct Options {
+    seeds: Vec<u64>,
+    codes: Vec<String>,
+    out: PathBuf,
+    assets: PathBuf,
+    language: Option<PathBuf>,
+    palettes: Option<PathBuf>,
+    canvas: usize,
+    scale: u32,
+    columns: u32,
+    atlas: bool,
+    pattern: Option<&'static HeadPattern>,
+    version: GeneratorVersion,
+    reroll: GreacherParts,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> anyhow::Result<Options> {
+        let mut options = Options {
+            seeds: vec![],
+            codes: vec![],
+            out: PathBuf::from("greacher-gen"),
+            assets: PathBuf::from("assets"),
+            language: None,
+            palettes: None,
+            canvas: GREACHER_CANVAS_SIZE,
+            scale: 8,
+            columns: 8,
+            atlas: false,
+            pattern: None,
+            version: GeneratorVersion::LATEST,
+            reroll: GreacherParts::none(),
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or_else(|| anyhow!("{} needs a value", arg));
+
+            match arg.as_str() {
+                "--seed" => options.seeds = vec![value()?.parse()?],
+                "--seeds" => {
+                    let range = value()?;
+                    let (from, to) = range
+                        .split_once("..")
+                        .ok_or_else(|| anyhow!("Seed ranges look like FROM..TO"))?;
+                    options.seeds = (from.parse()?..to.parse()?).collect();
+                }
+                "--count" => {
+                    options.seeds = (0..value()?.parse::<usize>()?)
+                        .map(|_| rand::random())
+                        .collect()
+                }
+                "--code" => options.codes.push(value()?),
+                "--out" => options.out = value()?.into(),
+                "--assets" => options.assets = value()?.into(),
+                "--language" => options.language = Some(value()?.into()),
+                "--palettes" => options.palettes = Some(value()?.into()),
+                "--canvas" => options.canvas = value()?.parse()?,
+                "--scale" => options.scale = value()?.parse()?,
+                "--columns" => options.columns = value()?.parse()?,
+                "--atlas" => options.atlas = true,
+                "--pattern" => {
+                    let name = value()?;
+                    options.pattern = Some(find_head_pattern(&name).ok_or_else(|| {
+                        let names: Vec<&str> =
+                            HEAD_PATTERNS.iter().map(|pattern| pattern.name).collect();
+
+                        anyhow!("Unknown pattern {}, pick one of {}", name, names.join(", "))
+                    })?);
+                }
+                "--generator" => {
+                    let version = value()?.trim_start_matches('v').parse()?;
+                    options.version = GeneratorVersion::from_u8(version).ok_or_else(|| {
+                        anyhow!(
+                            "Unknown generator version {}, the latest is {}",
+                            version,
+                            GeneratorVersion::LATEST as u8
+                        )
+                    })?;
+                }
+                "--reroll" => {
+                    for part in value()?.split(',') {
+                        options.reroll |= match part.trim() {
+                            "head" => GreacherParts::Head,
+                            "body" => GreacherParts::Body,
+                            "stats" => GreacherParts::Stats,
+                            "name" => GreacherParts::Name,
+                            "palette" => GreacherParts::Palette,
+                            _ => bail!(
+                                "Unknown part {}, pick from head, body, stats, name and palette",
+                                part
+                            ),
+                        };
+                    }
+                }
+                "--help" | "-h" => {
+                    print!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ => bail!("Unknown argument {}\n\n{}", arg, USAGE),
+            }
+        }
+
+        if options.seeds.is_empty() && options.codes.is_empty() {
+            options.seeds = (0..16).map(|_| rand::random()).collect();
+        }
+
+        if options.canvas < MIN_CANVAS_SIZE || options.scale == 0 || options.columns == 0 {
+            bail!(
+                "--canvas has to be at least {}, and --scale and --columns above 0",
+                MIN_CANVAS_SIZE
+            );
+        }
+
+        Ok(options)
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let options = Options::parse(std::env::args().skip(1))?;
+
+    let palettes = load_palettes(
+        &options
+            .palettes
+            .clone()
+            .unwrap_or_else(|| options.assets.join(GreacherPalettes::PATH)),
+    )?;
+    let language = match &options.language {
+        Some(path) => NameLanguage::from_ron(
+            &fs::read(path).with_context(|| format!("Couldn't read {}", path.display()))?,
+        )?,
+        None => NameLanguage::builtin(),
+    };
+    let font = FontVec::try_from_vec(fs::read(options.assets.join("fonts/04b03.ttf"))?)?;
+
+    let rarity_weights = RarityWeights::default();
+
+    fs::create_dir_all(&options.out)?;
+
+    let mut heads = vec![];
+
+    for &seed in &options.seeds {
+        let mut head = create_head_image(options.canvas);
+        let mut greacher = Greacher::from_versioned_genome(
+            Genome::from_seed(seed),
+            options.version,
+            &mut head,
+            &palettes,
+            &language,
+            &rarity_weights,
+        );
+        greacher.regenerate_parts(options.reroll, &mut head, &palettes, &language);
+
+        heads.push(export_head(greacher, head, &options)?);
+    }
+
+    for code in &options.codes {
+        let mut head = create_head_image(options.canvas);
+        let mut greacher = Greacher::from_code(code, &mut head, &palettes, &language)
+            .with_context(|| format!("Couldn't read code {}", code))?;
+        greacher.regenerate_parts(options.reroll, &mut head, &palettes, &language);
+
+        heads.push(export_head(greacher, head, &options)?);
+    }
+
+    let sheet = contact_sheet(&heads, &font, options.columns);
+    sheet.save(options.out.join("contact_sheet.png"))?;
+
+    println!(
+        "Wrote {} heads and a contact sheet to {}",
+        heads.len(),
+        options.out.display()
+    );
+
+    Ok(())
+}
+
+fn export_head(
+    mut greacher: Greacher,
+    mut head: Image,
+    options: &Options,
+) -> anyhow::Result<(Greacher, RgbaImage)> {
+    if let Some(pattern) = options.pattern {
+        generate_head_pattern_preview(
+            pattern,
+            &greacher.genome,
+            &mut head,
+            &greacher.palette.1,
+            greacher.traits,
+        );
+    }
+
+    let atlas = upscale(&head, options.scale)?;
+    let idle = imageops::crop_imm(&atlas, 0, 0, atlas.height(), atlas.height()).to_image();
+
+    if options.atlas {
+        atlas.save(options.out.join(format!("{}.png", greacher.genome.seed)))?;
+
+        if let Some(body) = greacher.generate_body() {
+            upscale(&body, options.scale)?.save(
+                options
+                    .out
+                    .join(format!("{}_body.png", greacher.genome.seed)),
+            )?;
+        }
+    } else {
+        idle.save(options.out.join(format!("{}.png", greacher.genome.seed)))?;
+    }
+
+    println!(
+        "{:>20}  {:<24} {:<10} {:<5} {}",
+        greacher.genome.seed,
+        greacher.name,
+        greacher.rarity.to_string(),
+        format!("{:.1}x", greacher.size),
+        greacher.to_code()?
+    );
+
+    Ok((greacher, idle))
+}
+
+fn load_palettes(path: &Path) -> anyhow::Result<GreacherPalettes> {
+    if let Some(format) = PaletteFormat::from_path(path) {
+        let bytes = fs::read(path).with_context(|| format!("Couldn't read {}", path.display()))?;
+        let set = format
+            .parse(&bytes, &PaletteSet::name_from_path(path))
+            .with_context(|| format!("Couldn't read palettes from {}", path.display()))?;
+
+        return Ok(GreacherPalettes::new(None, set.name, set.palettes));
+    }
+
+    let source = image::open(path)
+        .with_context(|| format!("Couldn't open {}", path.display()))?
+        .to_rgba8();
+
+    let image = Image::new(
+        Extent3d {
+            width: source.width(),
+            height: source.height(),
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        source.into_raw(),
+        TextureFormat::Rgba8UnormSrgb,
+    );
+
+    let palettes = GreacherPalettes::palettes_from_image(&image)
+        .with_context(|| format!("Couldn't read palettes from {}", path.display()))?;
+
+    // Named like the game names palette.png, so codes read the same.
+    let set = path
+        .file_name()
+        .map_or_else(String::new, |name| name.to_string_lossy().into_owned());
+
+    Ok(GreacherPalettes::new(None, set, palettes))
+}
+
+fn upscale(head: &Image, scale: u32) -> anyhow::Result<RgbaImage> {
+    let size = head.texture_descriptor.size;
+    let head = RgbaImage::from_raw(size.width, size.height, head.data.clone())
+        .ok_or_else(|| anyhow!("Generated image doesn't match its size"))?;
+
+    Ok(imageops::resize(
+        &head,
+        size.width * scale,
+        size.height * scale,
+        imageops::FilterType::Nearest,
+    ))
+}
+
+fn contact_sheet(heads: &[(Greacher, RgbaImage)], font: &FontVec, columns: u32) -> RgbaImage {
+    let head_size = heads
+        .iter()
+        .map(|(_, head)| head.width())
+        .max()
+        .unwrap_or(0);
+    let cell_width = head_size.max(MIN_CELL_WIDTH) + CELL_PADDING * 2;
+    let cell_height = head_size + LABEL_LINE_HEIGHT * 2 + CELL_PADDING * 3;
+
+    let columns = columns.min(heads.len().max(1) as u32);
+    let rows = (heads.len() as u32).div_ceil(columns);
+
+    let mut sheet = RgbaImage::from_pixel(
+        cell_width * columns,
+        (cell_height * rows).max(1),
+        SHEET_BACKGROUND,
+    );
+
+    for (i, (greacher, head)) in heads.iter().enumerate() {
+        let x = (i as u32 % columns) * cell_width;
+        let y = (i as u32 / columns) * cell_height;
+
+        imageops::overlay(
+            &mut sheet,
+            head,
+            (x + (cell_width - head.width()) / 2) as i64,
+            (y + CELL_PADDING + head_size - head.height()) as i64,
+        );
+
+        let label_y = y + CELL_PADDING * 2 + head_size;
+        let right = x + cell_width - CELL_PADDING;
+
+        draw_text(
+            &mut sheet,
+            font,
+            &greacher.name,
+            (x + CELL_PADDING, label_y),
+            right,
+            LABEL_COLOR,
+        );
+        draw_text(
+            &mut sheet,
+            font,
+            &format!("#{} {}", greacher.genome.seed, greacher.rarity),
+            (x + CELL_PADDING, label_y + LABEL_LINE_HEIGHT),
+            right,
+            SEED_COLOR,
+        );
+    }
+
+    sheet
+}
+
+/// Draws a line of text without anti-aliasing, cut off at `right`.
+fn draw_text(
+    image: &mut RgbaImage,
+    font: &FontVec,
+    text: &str,
+    position: (u32, u32),
+    right: u32,
+    color: Rgba<u8>,
+) {
+    let scale = PxScale::from(LABEL_FONT_SIZE);
+    let scaled_font = font.as_scaled(scale);
+    let mut caret = position.0 as f32;
+
+    for c in text.chars() {
+        let glyph_id = font.glyph_id(c);
+        let glyph = glyph_id.with_scale_and_position(
+            scale,
+            point(caret, position.1 as f32 + scaled_font.ascent()),
+        );
+        caret += scaled_font.h_advance(glyph_id);
+
+        let outline = match font.outline_glyph(glyph) {
+            Some(outline) => outline,
+            None => continue,
+        };
+        let bounds = outline.px_bounds();
+
+        outline.draw(|gx, gy, coverage| {
+            let x = bounds.min.x as i64 + gx as i64;
+            let y = bounds.min.y as i64 + gy as i64;
+
+            if coverage > 0.5
+                && x >= 0
+                && y >= 0
+                && (x as u32) < right.min(image.width())
+                && (y as u32) < image.height()
+            {
+                image.put_pixel(x as u32, y as u32, color);
+            }
+        });
+    }
+}
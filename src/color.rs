@@ -1,28 +1,77 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr};
 
-use bevy::{asset::LoadState, prelude::*, utils::HashMap};
+use bevy::{
+    asset::LoadState,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+    utils::HashMap,
+};
 use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::states::AppState;
+use crate::{
+    greachers::gen::GeneratorVersion,
+    index_keys::{IndexKeys, IndexKeysLoader},
+    messages::ShowMessage,
+    palette_swap::PaletteSwapPlugin,
+    palettes::{
+        PaletteError, PaletteLoadErrors, PaletteLocation, PaletteSet, PaletteSetLoader, PaletteSets,
+    },
+    states::AppState,
+};
 
 pub struct IndexerPlugin;
 
 impl Plugin for IndexerPlugin {
     fn build(&self, app: &mut App) {
+        let palette_load_errors = PaletteLoadErrors::default();
+
         app.insert_resource(
             IndexedImageServer::new()
                 .preload(vec!["indexed/wings.png".into(), "indexed/legs.png".into()]),
         )
         .insert_resource(GreacherPalettes::default())
+        .add_plugin(PaletteSwapPlugin)
+        .add_event::<ShowMessage>()
+        .add_event::<PalettesReloaded>()
+        .add_asset::<PaletteSet>()
+        .add_asset_loader(PaletteSetLoader::new(palette_load_errors.clone()))
+        .add_asset::<IndexKeys>()
+        .add_asset_loader(IndexKeysLoader::new(palette_load_errors.clone()))
+        .insert_resource(palette_load_errors)
+        .init_resource::<PalettesLoaded>()
+        .init_resource::<PaletteSets>()
+        .add_system(PaletteLoadErrors::report)
         .add_system_set(
             SystemSet::on_update(AppState::Loading)
                 .with_system(GreacherPalettes::init_color_palettes)
-                .with_system(IndexedImageServer::do_preload),
+                .with_system(
+                    IndexedImageServer::do_preload.after(GreacherPalettes::init_color_palettes),
+                ),
+        )
+        .add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(PaletteSets::cycle_palette_set)
+                .with_system(PaletteSets::reload_palettes)
+                .with_system(
+                    IndexedImageServer::reindex_changed_palettes
+                        .after(PaletteSets::reload_palettes),
+                )
+                .with_system(IndexedImageServer::reindex_changed_images)
+                .with_system(IndexedImageServer::fill_requested)
+                .with_system(IndexedImageServer::evict_unused),
         );
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// An sRGB color with straight alpha, one byte per channel, the way pixels
+/// of indexed images and palettes are stored. Bevy's own
+/// [`Color`](bevy::prelude::Color) is what's drawn with, and converts to and
+/// from this one.
+///
+/// Written as hex, like `#e9a416`, `#e9a41680` or `#fa0`, see
+/// [`Color::from_str`] and [`Display`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Color {
     r: u8,
     g: u8,
@@ -31,21 +80,245 @@ pub struct Color {
 }
 
 impl Color {
-    pub fn new(r: u8, g: u8, b: u8, a: u8) -> Color {
+    pub const TRANSPARENT: Color = Color::new(0, 0, 0, 0);
+    pub const BLACK: Color = Color::new(0, 0, 0, 255);
+    pub const WHITE: Color = Color::new(255, 255, 255, 255);
+
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Color {
         Color { r, g, b, a }
     }
 
-    pub fn from_raw(bytes: &[u8]) -> Color {
-        if bytes.len() != 4 {
-            panic!("Bad color raw format!");
+    pub const fn r(&self) -> u8 {
+        self.r
+    }
+
+    pub const fn g(&self) -> u8 {
+        self.g
+    }
+
+    pub const fn b(&self) -> u8 {
+        self.b
+    }
+
+    pub const fn a(&self) -> u8 {
+        self.a
+    }
+
+    pub const fn with_alpha(self, a: u8) -> Color {
+        Color { a, ..self }
+    }
+
+    /// Converts from a hue in degrees, and saturation and value from 0 to 1.
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Color {
+        let saturation = saturation.clamp(0.0, 1.0);
+        let value = value.clamp(0.0, 1.0);
+
+        let chroma = value * saturation;
+        let sector = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+
+        let (r, g, b) = match sector as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+
+        let channel = |c: f32| ((c + value - chroma) * 255.0).round() as u8;
+
+        Color::new(channel(r), channel(g), channel(b), 255)
+    }
+
+    /// Hue in degrees, and saturation and value from 0 to 1. Grays have a
+    /// hue of 0.
+    pub fn to_hsv(&self) -> (f32, f32, f32) {
+        let (hue, max, min) = self.hue();
+        let saturation = if max == 0.0 { 0.0 } else { (max - min) / max };
+
+        (hue, saturation, max)
+    }
+
+    /// Converts from a hue in degrees, and saturation and lightness from 0
+    /// to 1.
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Color {
+        let saturation = saturation.clamp(0.0, 1.0);
+        let lightness = lightness.clamp(0.0, 1.0);
+
+        let value = lightness + saturation * lightness.min(1.0 - lightness);
+        let saturation = if value == 0.0 {
+            0.0
+        } else {
+            2.0 * (1.0 - lightness / value)
+        };
+
+        Color::from_hsv(hue, saturation, value)
+    }
+
+    /// Hue in degrees, and saturation and lightness from 0 to 1. Grays have
+    /// a hue of 0.
+    pub fn to_hsl(&self) -> (f32, f32, f32) {
+        let (hue, max, min) = self.hue();
+        let lightness = (max + min) / 2.0;
+        let saturation = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * lightness - 1.0).abs())
+        };
+
+        (hue, saturation, lightness)
+    }
+
+    /// The hue in degrees, along with the largest and smallest channel from
+    /// 0 to 1, which the rest of HSV and HSL are made from.
+    fn hue(&self) -> (f32, f32, f32) {
+        let [r, g, b] = [self.r, self.g, self.b].map(|c| c as f32 / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let chroma = max - min;
+
+        let sector = if chroma == 0.0 {
+            0.0
+        } else if max == r {
+            ((g - b) / chroma).rem_euclid(6.0)
+        } else if max == g {
+            (b - r) / chroma + 2.0
+        } else {
+            (r - g) / chroma + 4.0
+        };
+
+        (sector * 60.0, max, min)
+    }
+
+    /// Relative luminance, from 0 for black to 1 for white.
+    pub fn luminance(&self) -> f32 {
+        0.2126 * to_linear(self.r) + 0.7152 * to_linear(self.g) + 0.0722 * to_linear(self.b)
+    }
+
+    /// How much the luminance of two colors stands apart, from 1 for none to
+    /// 21 for black and white. Text wants at least 4.5 against what's behind
+    /// it.
+    pub fn contrast_ratio(&self, other: Color) -> f32 {
+        let (a, b) = (self.luminance(), other.luminance());
+
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    /// The color `t` of the way to `other`, from 0 for this one to 1 for
+    /// `other`. Mixed in [`Oklab`], so the colors in between stay as bright
+    /// and saturated as the ends instead of going muddy.
+    pub fn lerp(self, other: Color, t: f32) -> Color {
+        let t = t.clamp(0.0, 1.0);
+        let (from, to) = (Oklab::from(self), Oklab::from(other));
+
+        Color::from(Oklab {
+            l: from.l + (to.l - from.l) * t,
+            a: from.a + (to.a - from.a) * t,
+            b: from.b + (to.b - from.b) * t,
+            alpha: from.alpha + (to.alpha - from.alpha) * t,
+        })
+    }
+
+    /// Averages colors weighted by how much of each to use, in [`Oklab`].
+    /// Colors without a positive weight are left out, and if none are left
+    /// there's nothing to mix.
+    pub fn mix(colors: impl IntoIterator<Item = (Color, f32)>) -> Option<Color> {
+        let mut total = 0.0;
+        let mut sum = Oklab {
+            l: 0.0,
+            a: 0.0,
+            b: 0.0,
+            alpha: 0.0,
+        };
+
+        for (color, weight) in colors {
+            if weight <= 0.0 {
+                continue;
+            }
+
+            let color = Oklab::from(color);
+            sum.l += color.l * weight;
+            sum.a += color.a * weight;
+            sum.b += color.b * weight;
+            sum.alpha += color.alpha * weight;
+            total += weight;
         }
 
-        Color {
-            r: bytes[0],
-            g: bytes[1],
-            b: bytes[2],
-            a: bytes[3],
+        if total <= 0.0 {
+            return None;
         }
+
+        Some(Color::from(Oklab {
+            l: sum.l / total,
+            a: sum.a / total,
+            b: sum.b / total,
+            alpha: sum.alpha / total,
+        }))
+    }
+
+    /// Mixes in white, from 0 for none to 1 for all of it.
+    pub fn tint(self, amount: f32) -> Color {
+        self.lerp(Color::WHITE.with_alpha(self.a), amount)
+    }
+
+    /// Mixes in black, from 0 for none to 1 for all of it.
+    pub fn shade(self, amount: f32) -> Color {
+        self.lerp(Color::BLACK.with_alpha(self.a), amount)
+    }
+
+    /// Takes away saturation, from 0 for none to 1 for a gray of the same
+    /// lightness.
+    pub fn desaturate(self, amount: f32) -> Color {
+        let mut color = Oklab::from(self);
+        let kept = 1.0 - amount.clamp(0.0, 1.0);
+
+        color.a *= kept;
+        color.b *= kept;
+
+        Color::from(color)
+    }
+
+    /// This color drawn on top of `below`, with the usual "normal" blending
+    /// of image editors.
+    pub fn over(self, below: Color) -> Color {
+        let top = self.a as f32 / 255.0;
+        let bottom = below.a as f32 / 255.0 * (1.0 - top);
+        let alpha = top + bottom;
+
+        if alpha == 0.0 {
+            return Color::TRANSPARENT;
+        }
+
+        let channel = |t: u8, b: u8| ((t as f32 * top + b as f32 * bottom) / alpha).round() as u8;
+
+        Color::new(
+            channel(self.r, below.r),
+            channel(self.g, below.g),
+            channel(self.b, below.b),
+            (alpha * 255.0).round() as u8,
+        )
+    }
+
+    pub fn is_opaque(&self) -> bool {
+        self.a == 255
+    }
+
+    /// Reads RGBA8 bytes, if there are exactly four of them.
+    pub fn from_raw(bytes: &[u8]) -> Option<Color> {
+        let [r, g, b, a]: [u8; 4] = bytes.try_into().ok()?;
+
+        Some(Color { r, g, b, a })
+    }
+
+    /// The pixels of RGBA8 image data, like [`Image::data`].
+    pub fn pixels(data: &[u8]) -> impl Iterator<Item = Color> + '_ {
+        data.chunks_exact(4).filter_map(Color::from_raw)
+    }
+
+    /// Packs colors into RGBA8 image data, the reverse of [`Color::pixels`].
+    pub fn to_pixels(colors: impl IntoIterator<Item = Color>) -> Vec<u8> {
+        colors.into_iter().flat_map(<[u8; 4]>::from).collect()
     }
 }
 
@@ -61,312 +334,1474 @@ impl From<Color> for [u8; 4] {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Eq)]
+impl From<[u8; 4]> for Color {
+    fn from([r, g, b, a]: [u8; 4]) -> Self {
+        Color { r, g, b, a }
+    }
+}
+
+impl From<Color> for bevy::prelude::Color {
+    fn from(val: Color) -> Self {
+        bevy::prelude::Color::rgba_u8(val.r, val.g, val.b, val.a)
+    }
+}
+
+impl From<bevy::prelude::Color> for Color {
+    /// Channels outside of 0 to 1, like those of HDR colors, are clamped.
+    fn from(color: bevy::prelude::Color) -> Self {
+        let [r, g, b, a] = color
+            .as_rgba_f32()
+            .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+
+        Color { r, g, b, a }
+    }
+}
+
+impl Display for Color {
+    /// Writes `#rrggbb`, or `#rrggbbaa` if the color isn't opaque.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+
+        if !self.is_opaque() {
+            write!(f, "{:02x}", self.a)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    /// Reads an `RRGGBB` or `RRGGBBAA` color, with or without a leading `#`.
+    /// The short `RGB` and `RGBA` forms have each digit doubled, so `#fa0`
+    /// is `#ffaa00`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let hex = text.strip_prefix('#').unwrap_or(text);
+
+        // Checked up front, since parsing the channels would allow signs.
+        if !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Err(ParseColorError(text.to_string()));
+        }
+
+        let digits = match hex.len() {
+            3 | 4 => 1,
+            6 | 8 => 2,
+            _ => return Err(ParseColorError(text.to_string())),
+        };
+
+        let channel = |i: usize| {
+            let value = u8::from_str_radix(&hex[i * digits..(i + 1) * digits], 16).unwrap();
+
+            if digits == 1 {
+                value * 0x11
+            } else {
+                value
+            }
+        };
+
+        Ok(Color::new(
+            channel(0),
+            channel(1),
+            channel(2),
+            if hex.len() == 4 * digits {
+                channel(3)
+            } else {
+                255
+            },
+        ))
+    }
+}
+
+/// Text that isn't a hex color.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseColorError(pub String);
+
+impl Display for ParseColorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "\"{}\" isn't a color, they're written #RRGGBB, #RRGGBBAA, #RGB or #RGBA",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(de::Error::custom)
+    }
+}
+
+/// Converts an sRGB channel to linear light, from 0 to 1.
+fn to_linear(channel: u8) -> f32 {
+    let c = channel as f32 / 255.0;
+
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts linear light back to an sRGB channel, clamping what's out of
+/// range.
+fn from_linear(c: f32) -> u8 {
+    let c = c.clamp(0.0, 1.0);
+    let c = if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    };
+
+    (c * 255.0).round() as u8
+}
+
+/// A color in the OKLab space, where the same distance looks like the same
+/// change in color anywhere. `l` is lightness from 0 to 1, `a` goes from
+/// green to red and `b` from blue to yellow. Alpha is kept from 0 to 1.
+///
+/// See <https://bottosson.github.io/posts/oklab/>.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Oklab {
+    pub l: f32,
+    pub a: f32,
+    pub b: f32,
+    pub alpha: f32,
+}
+
+impl From<Color> for Oklab {
+    // The matrices as published.
+    #[allow(clippy::excessive_precision)]
+    fn from(color: Color) -> Self {
+        let (r, g, b) = (to_linear(color.r), to_linear(color.g), to_linear(color.b));
+
+        let l = (0.4122214708 * r + 0.5363325363 * g + 0.0514459929 * b).cbrt();
+        let m = (0.2119034982 * r + 0.6806995451 * g + 0.1073969566 * b).cbrt();
+        let s = (0.0883024619 * r + 0.2817188376 * g + 0.6299787005 * b).cbrt();
+
+        Oklab {
+            l: 0.2104542553 * l + 0.7936177850 * m - 0.0040720468 * s,
+            a: 1.9779984951 * l - 2.4285922050 * m + 0.4505937099 * s,
+            b: 0.0259040371 * l + 0.7827717662 * m - 0.8086757660 * s,
+            alpha: color.a as f32 / 255.0,
+        }
+    }
+}
+
+impl From<Oklab> for Color {
+    /// Colors outside of what sRGB can show are clamped to it.
+    #[allow(clippy::excessive_precision)]
+    fn from(color: Oklab) -> Self {
+        let l = (color.l + 0.3963377774 * color.a + 0.2158037573 * color.b).powi(3);
+        let m = (color.l - 0.1055613458 * color.a - 0.0638541728 * color.b).powi(3);
+        let s = (color.l - 0.0894841775 * color.a - 1.2914855480 * color.b).powi(3);
+
+        Color::new(
+            from_linear(4.0767416621 * l - 3.3077115913 * m + 0.2309699292 * s),
+            from_linear(-1.2684380046 * l + 2.6097574011 * m - 0.3413193965 * s),
+            from_linear(-0.0041960863 * l - 0.7034186147 * m + 1.7076642189 * s),
+            (color.alpha.clamp(0.0, 1.0) * 255.0).round() as u8,
+        )
+    }
+}
+
+/// A ramp of colors from dark to light, which the key colors of indexed
+/// images are replaced with. Palettes have always been four colors, dark,
+/// darkish, basic and highlight, but can be any length.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct GreacherColorPalette {
-    pub dark: Color,
-    pub darkish: Color,
-    pub basic: Color,
-    pub highlight: Color,
+    pub colors: Vec<Color>,
 }
 
 impl GreacherColorPalette {
-    const DARK_MAP: Color = Color {
-        r: 0,
-        g: 0,
-        b: 255,
-        a: 255,
-    };
-    const DARKISH_MAP: Color = Color {
-        r: 0,
-        g: 255,
-        b: 0,
-        a: 255,
-    };
-    const BASIC_MAP: Color = Color {
-        r: 255,
-        g: 0,
-        b: 0,
-        a: 255,
-    };
-    const HIGHLIGHT_MAP: Color = Color {
-        r: 255,
-        g: 255,
-        b: 0,
-        a: 255,
-    };
+    /// How many colors the usual palettes have.
+    pub const CLASSIC_SLOTS: usize = 4;
 
-    pub fn from_seed(seed: u64) -> Self {
-        let mut rng = SmallRng::seed_from_u64(seed);
+    /// Chance of a generated palette getting a complementary accent.
+    const ACCENT_CHANCE: f64 = 0.25;
 
-        GreacherColorPalette {
-            dark: Color::new(
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                255,
-            ),
-            darkish: Color::new(
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                255,
-            ),
-            basic: Color::new(
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                255,
-            ),
-            highlight: Color::new(
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                255,
-            ),
+    pub fn new(colors: Vec<Color>) -> GreacherColorPalette {
+        GreacherColorPalette { colors }
+    }
+
+    /// A ramp of one hue from dark to highlight, shifting towards blue in the
+    /// shadows and towards yellow in the light the way hand made pixel art
+    /// ramps do. Some get a complementary accent as their highlight instead.
+    /// Each color is brighter than the one before it.
+    pub fn generate(rng: &mut impl Rng) -> Self {
+        let hue: f32 = rng.gen_range(0.0..360.0);
+        let saturation: f32 = rng.gen_range(0.45..0.85);
+        let hue_shift: f32 = rng.gen_range(8.0..24.0);
+        // Which way around the color wheel yellow is closer.
+        let towards_yellow = if (60.0 - hue).rem_euclid(360.0) < 180.0 {
+            1.0
+        } else {
+            -1.0
+        };
+
+        // Steps away from the middle of the ramp, how saturated they are
+        // compared to it, and the brightness they're picked from.
+        let steps = [
+            (-1.5, 0.95, 0.18..0.28),
+            (-0.5, 1.0, 0.40..0.50),
+            (0.5, 0.85, 0.62..0.74),
+            (1.5, 0.55, 0.86..1.0),
+        ];
+
+        let mut colors = steps.map(|(step, saturation_scale, values)| {
+            Color::from_hsv(
+                hue + step * hue_shift * towards_yellow,
+                saturation * saturation_scale,
+                rng.gen_range(values),
+            )
+        });
+
+        if rng.gen_bool(Self::ACCENT_CHANCE) {
+            colors[3] = Color::from_hsv(hue + 180.0, saturation * 0.7, rng.gen_range(0.9..1.0));
         }
+
+        // Hues differ in how bright they look, which the shifts and accents
+        // can throw off.
+        colors.sort_by(|a, b| a.luminance().total_cmp(&b.luminance()));
+
+        GreacherColorPalette::new(colors.to_vec())
     }
 
-    pub fn from_rng(rng: &mut SmallRng) -> Self {
-        GreacherColorPalette {
-            dark: Color::new(
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                255,
-            ),
-            darkish: Color::new(
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                255,
-            ),
-            basic: Color::new(
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                255,
-            ),
-            highlight: Color::new(
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                rng.gen_range(0..=255) / 16 * 16,
-                255,
-            ),
+    /// Reads RGBA8 colors, if there's a whole number of them and at least
+    /// one.
+    pub fn from_raw(bytes: &[u8]) -> Option<Self> {
+        if bytes.is_empty() || bytes.len() % 4 != 0 {
+            return None;
         }
+
+        Some(GreacherColorPalette::new(Color::pixels(bytes).collect()))
     }
 
-    pub fn from_raw(bytes: &[u8]) -> Self {
-        if bytes.len() != 16 {
-            panic!("Bad palette format!")
+    /// The color of `slot` in a ramp `slots` colors long. Palettes of
+    /// another length are picked from evenly, so the darkest and lightest
+    /// slots always get the darkest and lightest colors. Empty palettes are
+    /// transparent all the way.
+    pub fn ramp(&self, slot: usize, slots: usize) -> Color {
+        let Some(last) = self.colors.len().checked_sub(1) else {
+            return Color::TRANSPARENT;
         };
 
-        GreacherColorPalette {
-            dark: Color::from_raw(&bytes[0..4]),
-            darkish: Color::from_raw(&bytes[4..8]),
-            basic: Color::from_raw(&bytes[8..12]),
-            highlight: Color::from_raw(&bytes[12..16]),
-        }
+        // A single slot is the middle of the ramp.
+        let index = if slots <= 1 {
+            self.colors.len() / 2
+        } else {
+            // Rounded to the nearest color.
+            (2 * slot * last + slots - 1) / (2 * (slots - 1))
+        };
+
+        self.colors[index.min(last)]
     }
 
-    pub fn map(&self, color: Color) -> Color {
-        match color {
-            Self::DARK_MAP => self.dark,
-            Self::DARKISH_MAP => self.darkish,
-            Self::BASIC_MAP => self.basic,
-            Self::HIGHLIGHT_MAP => self.highlight,
-            _ => color,
+    /// The palette `t` of the way to `other`, slot by slot, see
+    /// [`Color::lerp`]. The result is as long as the longer of the two, with
+    /// the shorter one stretched to fit like [`GreacherColorPalette::ramp`]
+    /// does.
+    pub fn lerp(&self, other: &GreacherColorPalette, t: f32) -> GreacherColorPalette {
+        let slots = self.colors.len().max(other.colors.len());
+
+        GreacherColorPalette::new(
+            (0..slots)
+                .map(|slot| self.ramp(slot, slots).lerp(other.ramp(slot, slots), t))
+                .collect(),
+        )
+    }
+
+    /// Averages palettes weighted by how much of each to use, slot by slot,
+    /// see [`Color::mix`]. Lengths are evened out like in
+    /// [`GreacherColorPalette::lerp`]. There's nothing to blend without a
+    /// palette of positive weight.
+    pub fn blend(palettes: &[(&GreacherColorPalette, f32)]) -> Option<GreacherColorPalette> {
+        let slots = palettes
+            .iter()
+            .filter(|(_, weight)| *weight > 0.0)
+            .map(|(palette, _)| palette.colors.len())
+            .max()?;
+
+        let colors = (0..slots)
+            .map(|slot| {
+                Color::mix(
+                    palettes
+                        .iter()
+                        .map(|(palette, weight)| (palette.ramp(slot, slots), *weight)),
+                )
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(GreacherColorPalette::new(colors))
+    }
+
+    /// Every color mixed with white, see [`Color::tint`].
+    pub fn tint(&self, amount: f32) -> GreacherColorPalette {
+        self.map_colors(|color| color.tint(amount))
+    }
+
+    /// Every color mixed with black, see [`Color::shade`].
+    pub fn shade(&self, amount: f32) -> GreacherColorPalette {
+        self.map_colors(|color| color.shade(amount))
+    }
+
+    /// Every color made grayer, see [`Color::desaturate`].
+    pub fn desaturate(&self, amount: f32) -> GreacherColorPalette {
+        self.map_colors(|color| color.desaturate(amount))
+    }
+
+    fn map_colors(&self, f: impl Fn(Color) -> Color) -> GreacherColorPalette {
+        GreacherColorPalette::new(self.colors.iter().copied().map(f).collect())
+    }
+
+    /// Replaces one of the `keys` with the color of its slot. Other colors,
+    /// like outlines, are left as they are.
+    pub fn map(&self, keys: &IndexKeys, color: Color) -> Color {
+        match keys.slot(color) {
+            Some(slot) => self.ramp(slot, keys.keys.len()),
+            None => color,
         }
     }
 }
 
 impl Default for GreacherColorPalette {
     fn default() -> Self {
-        GreacherColorPalette {
-            dark: Color::new(255, 0, 0, 255),
-            darkish: Color::new(0, 255, 0, 255),
-            basic: Color::new(0, 0, 255, 255),
-            highlight: Color::new(255, 255, 0, 255),
-        }
+        GreacherColorPalette::new(vec![
+            Color::new(255, 0, 0, 255),
+            Color::new(0, 255, 0, 255),
+            Color::new(0, 0, 255, 255),
+            Color::new(255, 255, 0, 255),
+        ])
     }
 }
 
+/// Whether `palette.png` has been read, or given up on.
+#[derive(Default)]
+struct PalettesLoaded(bool);
+
+/// Sent when the file the palettes came from was edited and read again, so
+/// live greachers colored with them can be recolored.
+pub struct PalettesReloaded {
+    /// The palettes from before the edit.
+    pub previous: Vec<GreacherColorPalette>,
+}
+
+/// The palettes greachers are colored with: the loaded ones, followed by
+/// generated ones.
 pub struct GreacherPalettes {
     pub palette_source: Option<Handle<Image>>,
+    /// Name of the palette set the loaded palettes are from.
+    pub set: String,
     pub palettes: Vec<GreacherColorPalette>,
+    /// How many of the palettes were loaded, rather than generated.
+    pub loaded: usize,
 }
 
 impl Default for GreacherPalettes {
     fn default() -> Self {
-        Self { palette_source: Default::default(), palettes: vec![GreacherColorPalette::default()] }
+        GreacherPalettes::new(
+            None,
+            GreacherPalettes::DEFAULT_SET.to_string(),
+            vec![GreacherColorPalette::default()],
+        )
     }
 }
 
+/// Which palette a greacher is colored with, in a way that stays the same
+/// whatever palettes are loaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PaletteId {
+    /// A palette of the named set, by its index in the set.
+    Loaded { set: String, index: usize },
+    /// One of the generated palettes, which are the same for every set.
+    Generated(usize),
+}
+
 impl GreacherPalettes {
-    pub fn init_color_palettes(
+    pub const PATH: &'static str = "palette.png";
+    /// Name of the set made of just the default palette.
+    pub const DEFAULT_SET: &'static str = "default";
+
+    /// How many palettes are generated to go after the loaded ones.
+    pub const GENERATED: usize = 16;
+    /// How many palettes can be loaded, so the index of each one fits in a
+    /// greacher code.
+    pub const MAX_LOADED: usize = u16::MAX as usize + 1;
+    /// Seed of the generated palettes, which have to stay the same for
+    /// greacher codes to keep their colors.
+    const GENERATED_SEED: u64 = 0x9a1e_77e5;
+
+    pub fn new(
+        palette_source: Option<Handle<Image>>,
+        set: String,
+        loaded: Vec<GreacherColorPalette>,
+    ) -> GreacherPalettes {
+        let mut rng = SmallRng::seed_from_u64(Self::GENERATED_SEED);
+        let loaded_count = loaded.len();
+
+        let mut palettes = loaded;
+        palettes.extend((0..Self::GENERATED).map(|_| GreacherColorPalette::generate(&mut rng)));
+
+        GreacherPalettes {
+            palette_source,
+            set,
+            palettes,
+            loaded: loaded_count,
+        }
+    }
+
+    /// The lasting id of the palette at `index`.
+    pub fn id(&self, index: usize) -> PaletteId {
+        if index < self.loaded {
+            PaletteId::Loaded {
+                set: self.set.clone(),
+                index,
+            }
+        } else {
+            PaletteId::Generated(index - self.loaded)
+        }
+    }
+
+    /// Where the palette with the given id is, if its set is the loaded one
+    /// and it's there.
+    pub fn find(&self, id: &PaletteId) -> Option<usize> {
+        match id {
+            PaletteId::Loaded { set, index } if *set == self.set && *index < self.loaded => {
+                Some(*index)
+            }
+            PaletteId::Generated(index) if *index < Self::GENERATED => Some(self.loaded + index),
+            _ => None,
+        }
+    }
+
+    /// How many palettes greachers made with `version` pick from. Older
+    /// versions only know the loaded ones.
+    pub fn available(&self, version: GeneratorVersion) -> usize {
+        if version.has_generated_palettes() {
+            self.palettes.len()
+        } else {
+            self.loaded
+        }
+    }
+
+    /// Reads `palette.png` once it's loaded. A broken one leaves the default
+    /// palette in place and says what's wrong with it.
+    fn init_color_palettes(
+        mut done: ResMut<PalettesLoaded>,
         mut greacher_palettes: ResMut<GreacherPalettes>,
         asset_server: Res<AssetServer>,
         images: Res<Assets<Image>>,
+        mut messages: EventWriter<ShowMessage>,
     ) {
-        let palette_source: Handle<Image> = asset_server.load("palette.png");
+        if done.0 {
+            return;
+        }
 
-        let load_status = asset_server.get_load_state(&palette_source);
+        let palette_source: Handle<Image> = asset_server.load(Self::PATH);
 
-        if load_status == LoadState::Loading || load_status == LoadState::NotLoaded {
-            return;
-        } else if load_status == LoadState::Failed {
-            panic!("Failed to load palette! Is the assets folder present in the build?");
+        let palettes = match asset_server.get_load_state(&palette_source) {
+            LoadState::NotLoaded | LoadState::Loading => return,
+            LoadState::Failed => Err(format!(
+                "Couldn't load {}, is the assets folder there?",
+                Self::PATH
+            )),
+            _ => GreacherPalettes::palettes_from_image(images.get(&palette_source).unwrap())
+                .map_err(|why| format!("Couldn't read {}: {}", Self::PATH, why)),
+        };
+
+        done.0 = true;
+
+        match palettes {
+            Ok(palettes) => {
+                *greacher_palettes =
+                    GreacherPalettes::new(Some(palette_source), Self::PATH.to_string(), palettes);
+            }
+            Err(why) => {
+                error!("{}, using the default palette", why);
+                messages.send(ShowMessage(format!("{}. Using the default palette.", why)));
+
+                *greacher_palettes = GreacherPalettes::default();
+            }
         }
+    }
 
-        let palette = images.get(&palette_source).unwrap();
+    /// Reads palettes from an RGBA8 image, from every four pixels of each
+    /// row. Fully transparent groups of four are skipped, so rows can be
+    /// padded out to different lengths, and so can the pixels past the last
+    /// whole group.
+    pub fn palettes_from_image(palette: &Image) -> Result<Vec<GreacherColorPalette>, PaletteError> {
+        let descriptor = &palette.texture_descriptor;
+
+        match descriptor.format {
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => {}
+            format => return Err(PaletteError::UnsupportedFormat(format)),
+        }
 
-        dbg!(palette);
+        let (width, height) = (descriptor.size.width, descriptor.size.height);
 
-        let data = &palette.data;
+        if palette.data.len() != width as usize * height as usize * 4 {
+            return Err(PaletteError::BadImageSize {
+                width,
+                height,
+                bytes: palette.data.len(),
+            });
+        }
+
+        if width == 0 || height == 0 {
+            return Err(PaletteError::Empty);
+        }
+
+        let width = width as usize;
+        let slots = GreacherColorPalette::CLASSIC_SLOTS;
         let mut palettes = vec![];
 
-        for row_id in 0..(palette.texture_descriptor.size.height as usize) {
-            let row = &data[(row_id * 4 * 4)..(row_id * 4 * 4 + 16)];
+        for (row, pixels) in palette.data.chunks_exact(width * 4).enumerate() {
+            let colors: Vec<Color> = Color::pixels(pixels).collect();
+            let at = |column: usize| PaletteLocation {
+                row: row + 1,
+                column: column + 1,
+            };
+
+            for (group, colors) in colors.chunks(slots).enumerate() {
+                let start = group * slots;
+
+                if colors.iter().all(|color| color.a == 0) {
+                    continue;
+                }
+
+                if colors.len() < slots {
+                    return Err(PaletteError::PaletteSize {
+                        at: at(start),
+                        expected: slots,
+                        colors: colors.len(),
+                    });
+                }
 
-            palettes.push(GreacherColorPalette::from_raw(row));
+                if let Some(column) = colors.iter().position(|color| color.a == 0) {
+                    return Err(PaletteError::TransparentColor(at(start + column)));
+                }
+
+                palettes.push(GreacherColorPalette::new(colors.to_vec()));
+            }
         }
 
-        *greacher_palettes = GreacherPalettes {
-            palette_source: Some(palette_source),
-            palettes,
-        };
+        if palettes.is_empty() {
+            return Err(PaletteError::Empty);
+        }
+
+        if palettes.len() > GreacherPalettes::MAX_LOADED {
+            return Err(PaletteError::TooManyPalettes(palettes.len()));
+        }
+
+        Ok(palettes)
     }
 }
 
+/// Hands out copies of images drawn in [`IndexKeys`] colors, recolored with
+/// one of the palettes.
+#[derive(Default)]
 pub struct IndexedImageServer {
     preloaded: Vec<String>,
-    indexed_handles: HashMap<Handle<Image>, Vec<Handle<Image>>>,
+    /// The preloaded images, kept so they stay loaded.
+    preloaded_sources: Vec<Handle<Image>>,
+    /// Recolored copies of each image by their palette, so swapping to
+    /// other palettes doesn't leave any out of date. The copies are weak
+    /// handles, so ones nothing uses anymore get freed, and then forgotten
+    /// by [`IndexedImageServer::evict_unused`].
+    indexed_handles: HashMap<Handle<Image>, HashMap<GreacherColorPalette, Handle<Image>>>,
+    /// Copies handed out before their image finished loading, with the
+    /// palette to recolor them with once it has.
+    pending: Vec<(Handle<Image>, Handle<Image>, GreacherColorPalette)>,
+    /// The keys of each image, by a weak handle to it.
+    keys: HashMap<Handle<Image>, Handle<IndexKeys>>,
 }
 
 #[derive(Debug)]
 pub enum ImageIndexError {
     NotLoaded,
     LoadFailure,
+    PaletteOutOfRange {
+        index: usize,
+        palettes: usize,
+    },
+    /// The atlas cut out of it has no frames to draw.
+    NoFrames,
 }
 
 impl Display for ImageIndexError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            ImageIndexError::NotLoaded => {
-                f.write_str("NotLoaded")?;
-            }
-            ImageIndexError::LoadFailure => {
-                f.write_str("LoadFailure")?;
-            }
+            ImageIndexError::NotLoaded => f.write_str("It isn't loaded yet"),
+            ImageIndexError::LoadFailure => f.write_str("It couldn't be loaded"),
+            ImageIndexError::PaletteOutOfRange { index, palettes } => write!(
+                f,
+                "There's no palette {}, only {} are loaded",
+                index, palettes
+            ),
+            ImageIndexError::NoFrames => f.write_str("Its atlas has no frames"),
         }
-
-        Ok(())
     }
 }
 
+impl std::error::Error for ImageIndexError {}
+
 impl IndexedImageServer {
     pub fn new() -> Self {
-        IndexedImageServer {
-            preloaded: vec![],
-            indexed_handles: HashMap::new(),
-        }
+        IndexedImageServer::default()
     }
 
+    /// Images to load before the game starts, so the first greachers don't
+    /// wait on them.
     pub fn preload(mut self, paths: Vec<String>) -> Self {
         self.preloaded = paths;
         self
     }
 
+    /// Waits for the preloaded images and their keys. Images that fail to
+    /// load are reported, and greachers needing them are spawned without
+    /// them.
     fn do_preload(
         mut state: ResMut<State<AppState>>,
         mut server: ResMut<IndexedImageServer>,
-        greacher_palettes: Res<GreacherPalettes>,
+        palettes_loaded: Res<PalettesLoaded>,
         asset_server: Res<AssetServer>,
-        mut image_assets: ResMut<Assets<Image>>,
+        index_keys: Res<Assets<IndexKeys>>,
+        mut messages: EventWriter<ShowMessage>,
     ) {
-        for path in server.preloaded.clone() {
-            let result = server.generate_indexed_images(
-                &asset_server.load(&path),
-                &greacher_palettes,
-                &asset_server,
-                &mut image_assets,
-            );
+        // Greachers can't be made without palettes.
+        if !palettes_loaded.0 {
+            return;
+        }
+
+        if server.preloaded_sources.len() != server.preloaded.len() {
+            server.preloaded_sources = server
+                .preloaded
+                .iter()
+                .map(|path| asset_server.load(path.as_str()))
+                .collect();
+        }
 
-            match result {
-                Ok(_) => {}
-                Err(why) => match why {
-                    ImageIndexError::NotLoaded => {
-                        return; // not ready to index stuff yet.
-                    }
-                    ImageIndexError::LoadFailure => {
-                        panic!("Failed to preload indexed!");
-                    }
-                },
+        for source in server.preloaded_sources.clone() {
+            let keys = server.keys(&source, &asset_server, &index_keys);
+
+            if let Err(ImageIndexError::NotLoaded) =
+                check_load_state(&source, &asset_server).and(keys)
+            {
+                return; // not ready to index stuff yet.
+            }
+        }
+
+        for (path, source) in server.preloaded.iter().zip(&server.preloaded_sources) {
+            if let Err(why) = check_load_state(source, &asset_server) {
+                error!("Couldn't preload {}: {}", path, why);
+                messages.send(ShowMessage(format!("Couldn't load {}. {}.", path, why)));
             }
         }
 
         state.set(AppState::InGame).unwrap();
     }
 
-    pub fn get(&self, source: &Handle<Image>, palette_index: usize) -> Handle<Image> {
-        if !self.indexed_handles.contains_key(source) {
-            panic!("Image not loaded as indexed!")
+    /// A copy of the image at `path` recolored with a palette. If the image
+    /// or its keys are still loading, the copy is empty until they're done.
+    pub fn request(
+        &mut self,
+        path: &str,
+        palette_index: usize,
+        greacher_palettes: &GreacherPalettes,
+        asset_server: &AssetServer,
+        image_assets: &mut Assets<Image>,
+        index_keys: &Assets<IndexKeys>,
+    ) -> Result<Handle<Image>, ImageIndexError> {
+        self.request_source(
+            asset_server.load(path),
+            palette_index,
+            greacher_palettes,
+            asset_server,
+            image_assets,
+            index_keys,
+        )
+    }
+
+    /// Like [`IndexedImageServer::request`], for an image already being
+    /// loaded.
+    pub fn request_source(
+        &mut self,
+        source: Handle<Image>,
+        palette_index: usize,
+        greacher_palettes: &GreacherPalettes,
+        asset_server: &AssetServer,
+        image_assets: &mut Assets<Image>,
+        index_keys: &Assets<IndexKeys>,
+    ) -> Result<Handle<Image>, ImageIndexError> {
+        let palette = greacher_palettes.palettes.get(palette_index).ok_or(
+            ImageIndexError::PaletteOutOfRange {
+                index: palette_index,
+                palettes: greacher_palettes.palettes.len(),
+            },
+        )?;
+
+        self.request_recolored(source, palette, asset_server, image_assets, index_keys)
+    }
+
+    /// Like [`IndexedImageServer::request_source`], for any palette. Bred
+    /// greachers can have ones that aren't loaded anymore.
+    pub fn request_recolored(
+        &mut self,
+        source: Handle<Image>,
+        palette: &GreacherColorPalette,
+        asset_server: &AssetServer,
+        image_assets: &mut Assets<Image>,
+        index_keys: &Assets<IndexKeys>,
+    ) -> Result<Handle<Image>, ImageIndexError> {
+        // Images made at runtime have no load state, but are there already.
+        let loaded = image_assets.contains(&source);
+
+        if !loaded {
+            if let Err(ImageIndexError::LoadFailure) = check_load_state(&source, asset_server) {
+                return Err(ImageIndexError::LoadFailure);
+            }
+        }
+
+        let keys = self.keys(&source, asset_server, index_keys).ok();
+        let copies = self.indexed_handles.entry(source.clone()).or_default();
+
+        if let Some(copy) = copies.get(palette) {
+            // Weak handles outlive their images, until they're evicted.
+            if image_assets.contains(copy) {
+                return Ok(image_assets.get_handle(copy));
+            }
         }
 
-        self.indexed_handles[source][palette_index].clone()
+        let copy = if let (true, Some(keys)) = (loaded, keys) {
+            let source_image = image_assets.get(&source).unwrap();
+
+            image_assets.add(apply_palette(source_image, &keys, palette))
+        } else {
+            let copy = image_assets.add(Image::new_fill(
+                Extent3d::default(),
+                TextureDimension::D2,
+                &[0, 0, 0, 0],
+                TextureFormat::Rgba8UnormSrgb,
+            ));
+            self.pending
+                .push((source, copy.clone_weak(), palette.clone()));
+
+            copy
+        };
+
+        copies.insert(palette.clone(), copy.clone_weak());
+
+        Ok(copy)
     }
 
-    fn generate_indexed_images(
+    /// The keys `source` is drawn in, from the `.keys.ron` file next to it.
+    /// Images without one, or made at runtime, have [`IndexKeys::CLASSIC`].
+    pub fn keys(
         &mut self,
         source: &Handle<Image>,
-        greacher_palettes: &GreacherPalettes,
-        asset_server: &Res<AssetServer>,
-        image_assets: &mut ResMut<Assets<Image>>,
-    ) -> Result<(), ImageIndexError> {
-        if self.indexed_handles.contains_key(source) {
-            return Ok(());
+        asset_server: &AssetServer,
+        index_keys: &Assets<IndexKeys>,
+    ) -> Result<IndexKeys, ImageIndexError> {
+        let path = match asset_server.get_handle_path(source) {
+            Some(path) => path,
+            None => return Ok(IndexKeys::default()),
+        };
+
+        let handle = self
+            .keys
+            .entry(source.clone_weak())
+            .or_insert_with(|| asset_server.load(IndexKeys::path_for(path.path())));
+
+        if let Some(keys) = index_keys.get(handle) {
+            return Ok(keys.clone());
         }
 
-        let load_status = asset_server.get_load_state(source);
+        // Broken keys files are reported by their loader.
+        match asset_server.get_load_state(&*handle) {
+            LoadState::Failed => Ok(IndexKeys::default()),
+            _ => Err(ImageIndexError::NotLoaded),
+        }
+    }
+
+    /// The keys file of `source`, once [`IndexedImageServer::keys`] has been
+    /// asked for its keys.
+    pub fn keys_handle(&self, source: &Handle<Image>) -> Option<&Handle<IndexKeys>> {
+        self.keys.get(source)
+    }
+
+    /// Recolors the copies handed out before their image and keys loaded.
+    fn fill_requested(
+        mut server: ResMut<IndexedImageServer>,
+        asset_server: Res<AssetServer>,
+        mut image_assets: ResMut<Assets<Image>>,
+        index_keys: Res<Assets<IndexKeys>>,
+    ) {
+        let pending = std::mem::take(&mut server.pending);
+
+        server.pending = pending
+            .into_iter()
+            .filter(|(source, copy, palette)| {
+                let keys = match server.keys(source, &asset_server, &index_keys) {
+                    Ok(keys) => keys,
+                    Err(_) => return true,
+                };
+
+                server.fill(
+                    source,
+                    copy,
+                    &keys,
+                    palette,
+                    &asset_server,
+                    &mut image_assets,
+                )
+            })
+            .collect();
+    }
+
+    /// Recolors a pending copy if `source` is loaded, and says whether it's
+    /// still pending.
+    fn fill(
+        &self,
+        source: &Handle<Image>,
+        copy: &Handle<Image>,
+        keys: &IndexKeys,
+        palette: &GreacherColorPalette,
+        asset_server: &AssetServer,
+        image_assets: &mut Assets<Image>,
+    ) -> bool {
+        if let Some(source_image) = image_assets.get(source) {
+            let recolored = apply_palette(source_image, keys, palette);
+
+            // Nothing needs the copy anymore if it's gone.
+            if let Some(image) = image_assets.get_mut(copy) {
+                *image = recolored;
+            }
 
-        if load_status == LoadState::Loading  || load_status == LoadState::NotLoaded{
-            return Err(ImageIndexError::NotLoaded);
-        } else if load_status == LoadState::Failed {
-            return Err(ImageIndexError::LoadFailure);
+            return false;
         }
 
-        let source_image = image_assets.get(source).unwrap().clone();
+        match check_load_state(source, asset_server) {
+            Err(ImageIndexError::LoadFailure) => {
+                let path = asset_server.get_handle_path(source);
+                let path = path.as_ref().map(|path| path.path());
 
-        let mut indexed_images = vec![];
+                error!(
+                    "Couldn't recolor {:?}: {}",
+                    path,
+                    ImageIndexError::LoadFailure
+                );
 
-        for palette in &greacher_palettes.palettes {
-            indexed_images.push(self.generate_indexed_image(&source_image, palette));
+                false
+            }
+            _ => true,
         }
+    }
 
-        let mut indexed_handles = vec![];
+    /// Forgets copies that were freed, since nothing used them anymore, and
+    /// images that have no copies left.
+    fn evict_unused(
+        mut server: ResMut<IndexedImageServer>,
+        mut events: EventReader<AssetEvent<Image>>,
+    ) {
+        let mut evicted = false;
 
-        for image in indexed_images {
-            indexed_handles.push(image_assets.add(image));
+        for event in events.iter() {
+            if let AssetEvent::Removed { handle } = event {
+                for copies in server.indexed_handles.values_mut() {
+                    let before = copies.len();
+                    copies.retain(|_, copy| copy != handle);
+                    evicted |= copies.len() != before;
+                }
+            }
         }
 
-        self.indexed_handles.insert(source.clone(), indexed_handles);
+        if evicted {
+            server
+                .indexed_handles
+                .retain(|_, copies| !copies.is_empty());
+        }
+    }
 
-        Ok(())
+    /// Recolors the copies again when the palettes are reloaded, writing
+    /// over the ones live greachers already use. Copies in palettes that
+    /// stayed the same, or that are from other sets, are left alone.
+    fn reindex_changed_palettes(
+        mut server: ResMut<IndexedImageServer>,
+        mut reloaded: EventReader<PalettesReloaded>,
+        greacher_palettes: Res<GreacherPalettes>,
+        asset_server: Res<AssetServer>,
+        mut image_assets: ResMut<Assets<Image>>,
+        index_keys: Res<Assets<IndexKeys>>,
+    ) {
+        for reloaded in reloaded.iter() {
+            for source in server.indexed_handles.keys().cloned().collect::<Vec<_>>() {
+                server.recolor_in_place(
+                    &source,
+                    Some((&reloaded.previous, &greacher_palettes.palettes)),
+                    &asset_server,
+                    &mut image_assets,
+                    &index_keys,
+                );
+            }
+        }
+    }
+
+    /// Moves the copies in reloaded palettes over to the palettes' new
+    /// colors, and returns the ones that moved.
+    fn rekey_reloaded(
+        copies: &mut HashMap<GreacherColorPalette, Handle<Image>>,
+        previous: &[GreacherColorPalette],
+        palettes: &[GreacherColorPalette],
+    ) -> Vec<(Handle<Image>, GreacherColorPalette)> {
+        let mut moved = vec![];
+
+        for (old, new) in previous.iter().zip(palettes) {
+            if old == new {
+                continue;
+            }
+
+            if let Some(copy) = copies.remove(old) {
+                copies.entry(new.clone()).or_insert_with(|| copy.clone());
+                moved.push((copy, new.clone()));
+            }
+        }
+
+        moved
     }
 
-    fn generate_indexed_image(&mut self, source: &Image, palette: &GreacherColorPalette) -> Image {
-        let mut data = vec![];
+    /// Recolors the copies of images, or their keys, edited on disk in
+    /// place, which updates the sprites already showing them.
+    fn reindex_changed_images(
+        mut server: ResMut<IndexedImageServer>,
+        mut image_events: EventReader<AssetEvent<Image>>,
+        mut keys_events: EventReader<AssetEvent<IndexKeys>>,
+        asset_server: Res<AssetServer>,
+        mut image_assets: ResMut<Assets<Image>>,
+        index_keys: Res<Assets<IndexKeys>>,
+    ) {
+        let mut modified: Vec<Handle<Image>> = image_events
+            .iter()
+            .filter_map(|event| match event {
+                AssetEvent::Modified { handle } => Some(handle.clone()),
+                _ => None,
+            })
+            .collect();
+
+        for event in keys_events.iter() {
+            if let AssetEvent::Modified { handle } = event {
+                modified.extend(
+                    server
+                        .keys
+                        .iter()
+                        .filter(|(_, keys)| *keys == handle)
+                        .map(|(source, _)| source.clone()),
+                );
+            }
+        }
+
+        for source in modified {
+            if server.indexed_handles.contains_key(&source) {
+                server.recolor_in_place(
+                    &source,
+                    None,
+                    &asset_server,
+                    &mut image_assets,
+                    &index_keys,
+                );
+            }
+        }
+    }
 
-        let chunks = source.data.chunks_exact(4);
+    /// Writes the copies of `source` over with their palettes, or only the
+    /// ones in reloaded palettes with the palettes' new colors if the
+    /// palettes from before and after the reload are given.
+    fn recolor_in_place(
+        &mut self,
+        source: &Handle<Image>,
+        reloaded: Option<(&[GreacherColorPalette], &[GreacherColorPalette])>,
+        asset_server: &AssetServer,
+        image_assets: &mut Assets<Image>,
+        index_keys: &Assets<IndexKeys>,
+    ) {
+        let copies = self.indexed_handles.get_mut(source).unwrap();
+        let recolor = match reloaded {
+            Some((previous, palettes)) => Self::rekey_reloaded(copies, previous, palettes),
+            None => copies
+                .iter()
+                .map(|(palette, copy)| (copy.clone(), palette.clone()))
+                .collect(),
+        };
 
-        for color in chunks {
-            data.append(&mut (palette.map(Color::from_raw(color))).into());
+        // Copies of images still loading are filled in once they're done,
+        // so only their palettes need to change.
+        for (_, copy, palette) in &mut self.pending {
+            if let Some((_, new)) = recolor.iter().find(|(moved, _)| moved == copy) {
+                *palette = new.clone();
+            }
         }
 
+        let source_image = match image_assets.get(source) {
+            Some(source_image) => source_image.clone(),
+            None => return,
+        };
+        let keys = match self.keys(source, asset_server, index_keys) {
+            Ok(keys) => keys,
+            Err(_) => return,
+        };
+
+        for (copy, palette) in recolor {
+            if let Some(image) = image_assets.get_mut(&copy) {
+                *image = apply_palette(&source_image, &keys, &palette);
+            }
+        }
+    }
+}
+
+fn check_load_state(
+    source: &Handle<Image>,
+    asset_server: &AssetServer,
+) -> Result<(), ImageIndexError> {
+    match asset_server.get_load_state(source) {
+        LoadState::NotLoaded | LoadState::Loading => Err(ImageIndexError::NotLoaded),
+        LoadState::Failed => Err(ImageIndexError::LoadFailure),
+        _ => Ok(()),
+    }
+}
+
+/// Copies an image drawn in `keys` colors, with those replaced by the
+/// palette's. The CPU counterpart of
+/// [`PaletteSwapMaterial`](crate::palette_swap::PaletteSwapMaterial).
+pub fn apply_palette(source: &Image, keys: &IndexKeys, palette: &GreacherColorPalette) -> Image {
+    let data = Color::to_pixels(Color::pixels(&source.data).map(|color| palette.map(keys, color)));
+
+    Image::new(
+        source.texture_descriptor.size,
+        source.texture_descriptor.dimension,
+        data,
+        source.texture_descriptor.format,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+    /// An image `width` pixels wide of the given `rows`.
+    fn image(width: u32, rows: &[&[[u8; 4]]]) -> Image {
         Image::new(
-            source.texture_descriptor.size,
-            source.texture_descriptor.dimension,
-            data,
-            source.texture_descriptor.format,
+            Extent3d {
+                width,
+                height: rows.len() as u32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            rows.iter()
+                .flat_map(|row| row.iter().flatten())
+                .copied()
+                .collect(),
+            TextureFormat::Rgba8UnormSrgb,
         )
     }
+
+    fn gray(value: u8) -> [u8; 4] {
+        [value, value, value, 255]
+    }
+
+    fn ramp(from: u8) -> [[u8; 4]; 4] {
+        [gray(from), gray(from + 1), gray(from + 2), gray(from + 3)]
+    }
+
+    fn firsts(palettes: &[GreacherColorPalette]) -> Vec<u8> {
+        palettes.iter().map(|palette| palette.colors[0].r).collect()
+    }
+
+    const RED: Color = Color::new(230, 40, 30, 255);
+    const BLUE: Color = Color::new(20, 60, 220, 255);
+
+    #[test]
+    fn oklab_round_trips() {
+        for r in (0..=255).step_by(5) {
+            for g in (0..=255).step_by(5) {
+                for b in (0..=255).step_by(5) {
+                    let color = Color::new(r, g, b, r ^ g ^ b);
+
+                    assert_eq!(Color::from(Oklab::from(color)), color);
+                }
+            }
+        }
+
+        let white = Oklab::from(Color::WHITE);
+        assert!((white.l - 1.0).abs() < 1e-3);
+        assert!(white.a.abs() < 1e-3 && white.b.abs() < 1e-3);
+        assert_eq!(Oklab::from(Color::BLACK).l, 0.0);
+    }
+
+    #[test]
+    fn lerps_keep_their_ends() {
+        for (from, to) in [(RED, BLUE), (Color::BLACK, Color::WHITE), (BLUE, RED)] {
+            assert_eq!(from.lerp(to, 0.0), from);
+            assert_eq!(from.lerp(to, 1.0), to);
+            assert_eq!(from.lerp(to, -1.0), from);
+            assert_eq!(from.lerp(to, 2.0), to);
+        }
+
+        // Halfway in lightness, which is darker than halfway in sRGB.
+        assert_eq!(
+            Color::BLACK.lerp(Color::WHITE, 0.5),
+            Color::new(99, 99, 99, 255)
+        );
+        assert_eq!(
+            Color::WHITE.with_alpha(0).lerp(Color::WHITE, 0.5),
+            Color::WHITE.with_alpha(128)
+        );
+    }
+
+    #[test]
+    fn mixes_weigh_their_colors() {
+        assert_eq!(Color::mix([]), None);
+        assert_eq!(Color::mix([(RED, 0.0), (BLUE, -1.0)]), None);
+        assert_eq!(Color::mix([(RED, 2.0)]), Some(RED));
+        assert_eq!(Color::mix([(RED, 1.0), (BLUE, 0.0)]), Some(RED));
+        assert_eq!(
+            Color::mix([(RED, 1.0), (BLUE, 1.0)]),
+            Some(RED.lerp(BLUE, 0.5))
+        );
+        assert_eq!(
+            Color::mix([(RED, 3.0), (BLUE, 1.0)]),
+            Some(RED.lerp(BLUE, 0.25))
+        );
+    }
+
+    #[test]
+    fn tints_shades_and_desaturation() {
+        let faded = RED.with_alpha(100);
+
+        assert_eq!(RED.tint(0.0), RED);
+        assert_eq!(RED.tint(1.0), Color::WHITE);
+        assert_eq!(faded.tint(1.0), Color::WHITE.with_alpha(100));
+        assert_eq!(RED.shade(0.0), RED);
+        assert_eq!(RED.shade(1.0), Color::BLACK);
+        assert_eq!(faded.shade(1.0), Color::BLACK.with_alpha(100));
+
+        let lighter = RED.tint(0.5);
+        let darker = RED.shade(0.5);
+        assert!(lighter.luminance() > RED.luminance());
+        assert!(darker.luminance() < RED.luminance());
+
+        assert_eq!(RED.desaturate(0.0), RED);
+        assert_eq!(faded.desaturate(1.0).a(), 100);
+        for color in [RED, BLUE, RED.desaturate(0.5)] {
+            let gray = color.desaturate(1.0);
+            let channels = [gray.r(), gray.g(), gray.b()];
+
+            assert!(channels.iter().max().unwrap() - channels.iter().min().unwrap() <= 1);
+            assert!((Oklab::from(gray).l - Oklab::from(color).l).abs() < 0.01);
+        }
+
+        let (_, saturation, _) = RED.desaturate(0.5).to_hsv();
+        assert!(saturation < RED.to_hsv().1);
+    }
+
+    fn close(a: (f32, f32, f32), b: (f32, f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 0.5 && (a.1 - b.1).abs() < 0.01 && (a.2 - b.2).abs() < 0.01
+    }
+
+    #[test]
+    fn hsv_and_hsl() {
+        let cases = [
+            (Color::new(255, 0, 0, 255), (0.0, 1.0, 1.0), (0.0, 1.0, 0.5)),
+            (
+                Color::new(0, 255, 0, 255),
+                (120.0, 1.0, 1.0),
+                (120.0, 1.0, 0.5),
+            ),
+            (
+                Color::new(0, 0, 255, 255),
+                (240.0, 1.0, 1.0),
+                (240.0, 1.0, 0.5),
+            ),
+            (
+                Color::new(255, 0, 255, 255),
+                (300.0, 1.0, 1.0),
+                (300.0, 1.0, 0.5),
+            ),
+            (
+                Color::new(128, 64, 64, 255),
+                (0.0, 0.5, 0.502),
+                (0.0, 0.333, 0.376),
+            ),
+            (
+                Color::new(51, 204, 153, 255),
+                (160.0, 0.75, 0.8),
+                (160.0, 0.6, 0.5),
+            ),
+            (Color::BLACK, (0.0, 0.0, 0.0), (0.0, 0.0, 0.0)),
+            (Color::WHITE, (0.0, 0.0, 1.0), (0.0, 0.0, 1.0)),
+        ];
+
+        for (color, hsv, hsl) in cases {
+            assert!(
+                close(color.to_hsv(), hsv),
+                "{} is {:?}",
+                color,
+                color.to_hsv()
+            );
+            assert!(
+                close(color.to_hsl(), hsl),
+                "{} is {:?}",
+                color,
+                color.to_hsl()
+            );
+            assert_eq!(Color::from_hsv(hsv.0, hsv.1, hsv.2), color);
+            assert_eq!(Color::from_hsl(hsl.0, hsl.1, hsl.2), color);
+        }
+
+        // Hues wrap around, and the rest are clamped.
+        assert_eq!(
+            Color::from_hsv(480.0, 1.0, 1.0),
+            Color::from_hsv(120.0, 1.0, 1.0)
+        );
+        assert_eq!(
+            Color::from_hsv(-120.0, 1.0, 1.0),
+            Color::from_hsv(240.0, 1.0, 1.0)
+        );
+        assert_eq!(Color::from_hsv(0.0, 2.0, 1.5), Color::new(255, 0, 0, 255));
+        assert_eq!(
+            Color::from_hsl(0.0, -1.0, 0.5),
+            Color::new(128, 128, 128, 255)
+        );
+    }
+
+    #[test]
+    fn hsv_round_trips() {
+        for r in (0..=255).step_by(15) {
+            for g in (0..=255).step_by(15) {
+                for b in (0..=255).step_by(15) {
+                    let color = Color::new(r, g, b, 255);
+                    let (h, s, v) = color.to_hsv();
+                    let (hue, saturation, lightness) = color.to_hsl();
+
+                    assert_eq!(Color::from_hsv(h, s, v), color);
+                    assert_eq!(Color::from_hsl(hue, saturation, lightness), color);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn hex_colors() {
+        let parse = |text: &str| text.parse::<Color>();
+
+        assert_eq!(parse("#e9a416"), Ok(Color::new(0xe9, 0xa4, 0x16, 255)));
+        assert_eq!(parse("E9A416"), Ok(Color::new(0xe9, 0xa4, 0x16, 255)));
+        assert_eq!(parse("#e9a41680"), Ok(Color::new(0xe9, 0xa4, 0x16, 0x80)));
+        assert_eq!(parse("e9a41680"), Ok(Color::new(0xe9, 0xa4, 0x16, 0x80)));
+        assert_eq!(parse("#fa0"), Ok(Color::new(0xff, 0xaa, 0x00, 255)));
+        assert_eq!(parse("Fa0"), Ok(Color::new(0xff, 0xaa, 0x00, 255)));
+        assert_eq!(parse("#fa08"), Ok(Color::new(0xff, 0xaa, 0x00, 0x88)));
+        assert_eq!(parse("fa08"), Ok(Color::new(0xff, 0xaa, 0x00, 0x88)));
+
+        for bad in [
+            "",
+            "#",
+            "#f",
+            "#ff",
+            "#fffff",
+            "#fffffff",
+            "#fffffffff",
+            "##fff",
+            "#ggg",
+            "#12345g",
+            "#+1+2+3",
+            "#+f+f+f+f",
+            "#ffé",
+            " #fff",
+            "#fff ",
+            "0x123456",
+        ] {
+            assert_eq!(parse(bad), Err(ParseColorError(bad.to_string())));
+        }
+    }
+
+    #[test]
+    fn hex_written_back() {
+        assert_eq!(Color::new(0xe9, 0xa4, 0x16, 255).to_string(), "#e9a416");
+        assert_eq!(Color::new(0xe9, 0xa4, 0x16, 0x80).to_string(), "#e9a41680");
+        assert_eq!(Color::TRANSPARENT.to_string(), "#00000000");
+
+        for color in [RED, BLUE.with_alpha(7), Color::TRANSPARENT] {
+            assert_eq!(color.to_string().parse(), Ok(color));
+        }
+    }
+
+    #[test]
+    fn serde_round_trips() {
+        for color in [RED, BLUE.with_alpha(7), Color::TRANSPARENT, Color::WHITE] {
+            let text = ron::to_string(&color).unwrap();
+
+            assert_eq!(text, format!("\"{}\"", color));
+            assert_eq!(ron::from_str::<Color>(&text).unwrap(), color);
+        }
+
+        assert_eq!(
+            ron::from_str::<Color>("\"#fa0\"").unwrap(),
+            Color::new(0xff, 0xaa, 0x00, 255)
+        );
+        assert!(ron::from_str::<Color>("\"#fa\"").is_err());
+        assert!(ron::from_str::<Color>("(r: 1, g: 2, b: 3, a: 4)").is_err());
+    }
+
+    #[test]
+    fn palettes_blend_slot_by_slot() {
+        let long = GreacherColorPalette::new(vec![Color::BLACK, RED, BLUE, Color::WHITE]);
+        let short = GreacherColorPalette::new(vec![BLUE, RED]);
+
+        assert_eq!(long.lerp(&short, 0.0), long);
+        assert_eq!(
+            long.lerp(&short, 1.0).colors,
+            [BLUE, BLUE, RED, RED].to_vec()
+        );
+        assert_eq!(
+            GreacherColorPalette::blend(&[(&long, 1.0), (&short, 1.0)]),
+            Some(long.lerp(&short, 0.5))
+        );
+        assert_eq!(
+            GreacherColorPalette::blend(&[(&long, 0.0), (&short, 1.0)]),
+            Some(short.clone())
+        );
+        assert_eq!(GreacherColorPalette::blend(&[(&long, 0.0)]), None);
+        assert_eq!(long.shade(1.0).colors, [Color::BLACK; 4].to_vec());
+    }
+
+    #[test]
+    fn ramps_stretch_over_palettes() {
+        let palette = |length: u8| {
+            GreacherColorPalette::new(
+                (0..length)
+                    .map(|value| Color::new(value, 0, 0, 255))
+                    .collect(),
+            )
+        };
+        let ramp = |palette: &GreacherColorPalette, slots| -> Vec<u8> {
+            (0..slots).map(|slot| palette.ramp(slot, slots).r).collect()
+        };
+
+        assert_eq!(ramp(&palette(4), 4), [0, 1, 2, 3]);
+        assert_eq!(ramp(&palette(3), 4), [0, 1, 1, 2]);
+        assert_eq!(ramp(&palette(6), 4), [0, 2, 3, 5]);
+        assert_eq!(ramp(&palette(1), 4), [0, 0, 0, 0]);
+        assert_eq!(ramp(&palette(5), 1), [2]);
+
+        let empty = palette(0);
+        assert_eq!(empty.ramp(0, 4), Color::TRANSPARENT);
+        assert_eq!(empty.ramp(3, 4), Color::TRANSPARENT);
+        assert_eq!(empty.ramp(0, 1), Color::TRANSPARENT);
+        assert_eq!(empty.lerp(&empty, 0.5), empty);
+    }
+
+    #[test]
+    fn reloads_only_move_copies_of_changed_palettes() {
+        let palette = |value| GreacherColorPalette::new(vec![Color::new(value, value, value, 255)]);
+        let handle = || Handle::<Image>::weak(bevy::asset::HandleId::random::<Image>());
+
+        // Copies of the three reloaded palettes, and of one from another set.
+        let originals: Vec<_> = [0, 1, 2, 7]
+            .into_iter()
+            .map(|value| (palette(value), handle()))
+            .collect();
+        let mut copies: HashMap<_, _> = originals.iter().cloned().collect();
+
+        let moved = IndexedImageServer::rekey_reloaded(
+            &mut copies,
+            &[palette(0), palette(1), palette(2)],
+            &[palette(0), palette(9), palette(2), palette(3)],
+        );
+
+        assert_eq!(moved, [(originals[1].1.clone(), palette(9))]);
+        assert_eq!(copies.len(), 4);
+        assert_eq!(copies[&palette(9)], originals[1].1);
+        assert_eq!(copies[&palette(7)], originals[3].1);
+        assert!(!copies.contains_key(&palette(1)));
+    }
+
+    #[test]
+    fn image_palettes_skip_padding() {
+        let [a, b, c, d] = ramp(10);
+        let row = [
+            a, b, c, d, CLEAR, CLEAR, CLEAR, CLEAR, a, b, c, d, CLEAR, CLEAR,
+        ];
+        let [e, f, g, h] = ramp(20);
+        let ragged = [
+            e, f, g, h, CLEAR, CLEAR, CLEAR, CLEAR, CLEAR, CLEAR, CLEAR, CLEAR, CLEAR, CLEAR,
+        ];
+        let empty = [CLEAR; 14];
+
+        let palettes =
+            GreacherPalettes::palettes_from_image(&image(14, &[&row, &empty, &ragged])).unwrap();
+
+        assert_eq!(firsts(&palettes), [10, 10, 20]);
+        assert_eq!(palettes[2].colors, ramp(20).map(Color::from).to_vec());
+    }
+
+    #[test]
+    fn image_palette_errors() {
+        let read = |width, rows: &[&[[u8; 4]]]| {
+            GreacherPalettes::palettes_from_image(&image(width, rows)).unwrap_err()
+        };
+        let at = |row, column| PaletteLocation { row, column };
+        let [a, b, c, d] = ramp(10);
+
+        assert!(matches!(
+            read(6, &[&[a, b, c, d, CLEAR, CLEAR], &[a, b, c, d, CLEAR, a]]),
+            PaletteError::PaletteSize { at: location, expected: 4, colors: 2 }
+                if location == at(2, 5)
+        ));
+        assert!(matches!(
+            read(8, &[&[a, b, c, d, a, b, c, d], &[a, b, c, d, a, CLEAR, c, d]]),
+            PaletteError::TransparentColor(location) if location == at(2, 6)
+        ));
+        assert!(matches!(
+            read(4, &[&[CLEAR; 4], &[CLEAR; 4]]),
+            PaletteError::Empty
+        ));
+        assert!(matches!(read(4, &[]), PaletteError::Empty));
+
+        let palette = [a, b, c, d];
+        let rows = vec![&palette[..]; GreacherPalettes::MAX_LOADED + 1];
+        assert!(matches!(
+            read(4, &rows),
+            PaletteError::TooManyPalettes(count) if count == GreacherPalettes::MAX_LOADED + 1
+        ));
+
+        let mut short = image(4, &[&[a, b, c, d]]);
+        short.data.pop();
+        assert!(matches!(
+            GreacherPalettes::palettes_from_image(&short).unwrap_err(),
+            PaletteError::BadImageSize {
+                width: 4,
+                height: 1,
+                bytes: 15
+            }
+        ));
+
+        let mut gray = image(4, &[&[a, b, c, d]]);
+        gray.texture_descriptor.format = TextureFormat::R8Unorm;
+        assert!(matches!(
+            GreacherPalettes::palettes_from_image(&gray).unwrap_err(),
+            PaletteError::UnsupportedFormat(TextureFormat::R8Unorm)
+        ));
+    }
 }
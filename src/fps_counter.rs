@@ -7,7 +7,7 @@ pub struct FpsCounterPlugin;
 
 impl Plugin for FpsCounterPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugin(FrameTimeDiagnosticsPlugin::default())
+        app.add_plugin(FrameTimeDiagnosticsPlugin)
             .add_startup_system(spawn_fps_counter)
             .add_system(fps_counter);
     }
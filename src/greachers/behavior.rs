@@ -1,15 +1,19 @@
 use std::time::Duration;
 
-use bevy::prelude::*;
+use bevy::{prelude::*, sprite::Anchor};
 use bevy_rapier2d::prelude::Velocity;
+use rand::{thread_rng, Rng};
 
 use crate::basics::components::MovementHistory;
 
 use super::{
     components::{
-        Greacher, GreacherAnimationState, GreacherBodyAnimation, GreacherBodyType, LegState,
+        Greacher, GreacherAnimationState, GreacherBodyAnimation, GreacherBodyType,
+        GreacherHeadAnimation, GreacherHover, GreacherStats, LegState,
     },
     game_plugin::WorldMouse,
+    gen::HeadFrame,
+    rarity::GreacherShimmer,
 };
 
 pub fn animate_greacher_body(
@@ -80,6 +84,48 @@ pub fn animate_greacher_body(
     }
 }
 
+pub fn animate_greacher_head(
+    time: Res<Time>,
+    world_mouse: Res<WorldMouse>,
+    mut heads: Query<(
+        &mut GreacherHeadAnimation,
+        &mut TextureAtlasSprite,
+        &Transform,
+        &Velocity,
+        &GreacherStats,
+    )>,
+) {
+    for (mut animation, mut sprite, transform, velocity, stats) in &mut heads {
+        animation.blink_timer.tick(time.delta());
+
+        if animation.blink_timer.just_finished() {
+            animation.blinking = !animation.blinking;
+
+            let next = if animation.blinking {
+                GreacherHeadAnimation::BLINK_DURATION
+            } else {
+                thread_rng().gen_range(GreacherHeadAnimation::BLINK_INTERVAL)
+            };
+            animation.blink_timer = Timer::from_seconds(next, false);
+        }
+
+        // Greachers chase the cursor whether or not they can see it, so the
+        // face goes by where they're actually heading.
+        let to_mouse = (**world_mouse - transform.translation.truncate()).normalize_or_zero();
+        let chasing = velocity.linvel.dot(to_mouse) > Greacher::STILL_EPSILON;
+
+        animation.expression = if !chasing {
+            HeadFrame::Idle
+        } else if velocity.linvel.length() > stats.speed * GreacherHeadAnimation::SQUINT_SPEED {
+            HeadFrame::Squint
+        } else {
+            HeadFrame::OpenMouth
+        };
+
+        sprite.index = animation.frame().index();
+    }
+}
+
 fn get_greacher_state(
     time: &Time,
     greacher: &Greacher,
@@ -102,14 +148,19 @@ fn get_greacher_state(
 pub fn go_towards_mouse(
     time: Res<Time>,
     world_mouse: Res<WorldMouse>,
-    mut greachers: Query<(&mut Velocity, &Transform), With<Greacher>>,
+    mut greachers: Query<(&mut Velocity, &Transform, &GreacherStats), With<Greacher>>,
 ) {
-    for (mut velocity, transform) in &mut greachers {
-        velocity.linvel += (**world_mouse
-            - Vec2::new(transform.translation.x, transform.translation.y))
-        .normalize_or_zero()
-            * 72.
-            * time.delta_seconds();
+    for (mut velocity, transform, stats) in &mut greachers {
+        let to_mouse = **world_mouse - Vec2::new(transform.translation.x, transform.translation.y);
+
+        // Greachers that can't see the cursor only wander its way.
+        let acceleration = if to_mouse.length() > stats.sight_radius {
+            stats.acceleration * Greacher::UNSEEN_ACCELERATION
+        } else {
+            stats.acceleration
+        };
+
+        velocity.linvel += to_mouse.normalize_or_zero() * acceleration * time.delta_seconds();
     }
 }
 
@@ -132,8 +183,53 @@ pub fn set_z(mut entities: Query<&mut Transform, With<Greacher>>) {
     }
 }
 
-pub fn limit_greacher_velocity(mut entities: Query<&mut Velocity>) {
-    for mut velocity in &mut entities {
-        velocity.linvel = velocity.linvel.clamp_length_max(16.);
+pub fn limit_greacher_velocity(mut entities: Query<(&mut Velocity, &GreacherStats)>) {
+    for (mut velocity, stats) in &mut entities {
+        velocity.linvel = velocity.linvel.clamp_length_max(stats.speed);
+    }
+}
+
+/// Bobs flying greachers up and down. Only the head and body sprites move,
+/// the rigid body itself is left to the physics.
+pub fn hover_flying_greachers(
+    time: Res<Time>,
+    mut greachers: Query<(&mut GreacherHover, &mut TextureAtlasSprite, &Children)>,
+    mut bodies: Query<&mut Transform, With<GreacherBodyAnimation>>,
+) {
+    for (mut hover, mut head, children) in &mut greachers {
+        let offset = (time.seconds_since_startup() as f32 * GreacherHover::FREQUENCY + hover.phase)
+            .sin()
+            * GreacherHover::AMPLITUDE;
+
+        // Lowering the anchor raises the sprite.
+        head.anchor = Anchor::Custom(Vec2::new(0., -offset / hover.head_size));
+
+        for child in children {
+            if let Ok(mut transform) = bodies.get_mut(*child) {
+                transform.translation.y += offset - hover.offset;
+            }
+        }
+
+        hover.offset = offset;
+    }
+}
+
+pub fn shimmer_greachers(
+    time: Res<Time>,
+    mut greachers: Query<(&GreacherShimmer, &mut TextureAtlasSprite, &Children)>,
+    mut bodies: Query<&mut TextureAtlasSprite, Without<GreacherShimmer>>,
+) {
+    for (shimmer, mut sprite, children) in &mut greachers {
+        let hue = (time.seconds_since_startup() as f32 * GreacherShimmer::SPEED + shimmer.phase)
+            .rem_euclid(360.);
+        let tint = Color::hsl(hue, 1.0, 0.85);
+
+        sprite.color = tint;
+
+        for child in children {
+            if let Ok(mut body) = bodies.get_mut(*child) {
+                body.color = tint;
+            }
+        }
     }
 }
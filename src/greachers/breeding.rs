@@ -0,0 +1,116 @@
+use bevy::prelude::*;
+
+use crate::{camera::GameWorldRenderLayer, color::GreacherPalettes, palette_swap::IndexedSprites};
+
+use super::{
+    components::Greacher,
+    game_plugin::{spawn_greacher, GreacherHeadImageTemplate, WorldMouse},
+    names::{NameLanguage, NameLanguages, NameRegistry},
+    rarity::RarityWeights,
+};
+
+pub struct BreedingConfig {
+    /// Chance for each gene of an offspring to be rerolled instead of inherited.
+    pub mutation_rate: f32,
+}
+
+impl Default for BreedingConfig {
+    fn default() -> Self {
+        BreedingConfig {
+            mutation_rate: 0.05,
+        }
+    }
+}
+
+/// Spawns a child of the two greachers halfway between them.
+pub struct BreedGreachers {
+    pub parents: (Entity, Entity),
+}
+
+pub fn pick_parents_near_mouse(
+    keys: Res<Input<KeyCode>>,
+    world_mouse: Res<WorldMouse>,
+    greachers: Query<(Entity, &Transform), With<Greacher>>,
+    mut breed_events: EventWriter<BreedGreachers>,
+) {
+    if !keys.just_pressed(KeyCode::B) {
+        return;
+    }
+
+    let mut nearest: Vec<(Entity, f32)> = greachers
+        .iter()
+        .map(|(entity, transform)| {
+            (
+                entity,
+                transform
+                    .translation
+                    .truncate()
+                    .distance_squared(**world_mouse),
+            )
+        })
+        .collect();
+
+    nearest.sort_by(|a, b| a.1.total_cmp(&b.1));
+
+    if let [(a, _), (b, _), ..] = nearest[..] {
+        breed_events.send(BreedGreachers { parents: (a, b) });
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn breed_greachers(
+    mut commands: Commands,
+    mut breed_events: EventReader<BreedGreachers>,
+    config: Res<BreedingConfig>,
+    greachers: Query<(&Greacher, &Transform)>,
+    asset_server: Res<AssetServer>,
+    mut indexed_sprites: IndexedSprites,
+    mut images: ResMut<Assets<Image>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    greacher_palettes: Res<GreacherPalettes>,
+    head_template: Res<GreacherHeadImageTemplate>,
+    game_world_render_layer: Res<GameWorldRenderLayer>,
+    name_languages: Res<NameLanguages>,
+    languages: Res<Assets<NameLanguage>>,
+    mut name_registry: ResMut<NameRegistry>,
+    rarity_weights: Res<RarityWeights>,
+) {
+    for event in breed_events.iter() {
+        let (a, a_transform) = match greachers.get(event.parents.0) {
+            Ok(parent) => parent,
+            Err(_) => continue,
+        };
+        let (b, b_transform) = match greachers.get(event.parents.1) {
+            Ok(parent) => parent,
+            Err(_) => continue,
+        };
+
+        let mut tex = head_template.0.clone();
+
+        let child = Greacher::breed(
+            a,
+            b,
+            config.mutation_rate,
+            &mut tex,
+            &greacher_palettes,
+            name_languages.current(&languages),
+            &rarity_weights,
+        );
+
+        let position =
+            (a_transform.translation.truncate() + b_transform.translation.truncate()) / 2.;
+
+        spawn_greacher(
+            &mut commands,
+            &asset_server,
+            &mut indexed_sprites,
+            &mut images,
+            &mut texture_atlases,
+            child,
+            tex,
+            position,
+            &game_world_render_layer,
+            &mut name_registry,
+        );
+    }
+}
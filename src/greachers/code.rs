@@ -0,0 +1,611 @@
+use std::fmt::Display;
+
+use crate::color::PaletteId;
+
+use super::{
+    components::GreacherBodyType, gen::GeneratorVersion, genome::Genome, rarity::GreacherRarity,
+};
+
+/// Crockford's base32 alphabet, which leaves out I, L, O and U so codes can't
+/// be misread.
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Characters between the dashes of a written out code.
+const GROUP_LENGTH: usize = 5;
+
+const FLAG_WINGS: u8 = 1 << 0;
+const FLAG_FULL_GENOME: u8 = 1 << 1;
+/// Two bits holding the index of the rarity tier.
+const RARITY_SHIFT: u8 = 2;
+const RARITY_MASK: u8 = 0b11 << RARITY_SHIFT;
+/// Set when the palette is a generated one, which has no set to name.
+const FLAG_GENERATED_PALETTE: u8 = 1 << 4;
+
+/// Everything needed to rebuild a greacher on another machine.
+///
+/// Wild greachers only need their seed, since every gene is rolled from it.
+/// Bred ones inherit genes the seed can't reproduce, so their codes carry the
+/// whole genome and come out longer. The palette and name language are kept
+/// by name, so they don't depend on which ones the reader has loaded.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GreacherCode {
+    pub version: GeneratorVersion,
+    pub genome: Genome,
+    pub palette: PaletteId,
+    /// Name of the language the greacher is named in.
+    pub language: String,
+    pub body_type: GreacherBodyType,
+    /// Stored so codes don't depend on the rarity weights of whoever reads
+    /// them.
+    pub rarity: GreacherRarity,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GreacherCodeError {
+    InvalidCharacter(char),
+    WrongLength(usize),
+    ChecksumMismatch,
+    UnsupportedVersion(u8),
+    UnknownFlags(u8),
+    PaletteOutOfRange {
+        index: usize,
+        palettes: usize,
+    },
+    /// Codes only have room for the first 65536 palettes of a set.
+    PaletteTooLarge(usize),
+    /// Palette set and language names can be up to 255 bytes long.
+    NameTooLong(String),
+    InvalidName,
+    UnknownPaletteSet(String),
+    UnknownLanguage(String),
+}
+
+impl Display for GreacherCodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GreacherCodeError::InvalidCharacter(c) => {
+                write!(f, "'{}' can't appear in a greacher code", c)
+            }
+            GreacherCodeError::WrongLength(length) => {
+                write!(f, "A greacher code can't be {} characters long", length)
+            }
+            GreacherCodeError::ChecksumMismatch => {
+                f.write_str("Greacher code is mistyped or corrupted")
+            }
+            GreacherCodeError::UnsupportedVersion(version) => write!(
+                f,
+                "Greacher code is from generator version {}, but this only knows up to {}",
+                version,
+                GeneratorVersion::LATEST as u8
+            ),
+            GreacherCodeError::UnknownFlags(flags) => {
+                write!(f, "Greacher code has unknown flags {:#04x}", flags)
+            }
+            GreacherCodeError::PaletteOutOfRange { index, palettes } => write!(
+                f,
+                "Greacher code uses palette {}, but only {} are loaded",
+                index, palettes
+            ),
+            GreacherCodeError::PaletteTooLarge(index) => write!(
+                f,
+                "Palette {} doesn't fit in a greacher code, which only holds up to {}",
+                index,
+                u16::MAX
+            ),
+            GreacherCodeError::NameTooLong(name) => write!(
+                f,
+                "\"{}\" doesn't fit in a greacher code, which holds names up to {} bytes long",
+                name,
+                u8::MAX
+            ),
+            GreacherCodeError::InvalidName => {
+                f.write_str("Greacher code has a palette set or language name that isn't text")
+            }
+            GreacherCodeError::UnknownPaletteSet(set) => write!(
+                f,
+                "Greacher code is colored with the {} palettes, which aren't loaded",
+                set
+            ),
+            GreacherCodeError::UnknownLanguage(language) => write!(
+                f,
+                "Greacher code is named in {}, which isn't loaded",
+                language
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GreacherCodeError {}
+
+impl GreacherCode {
+    /// Length of the version, flags and palette index at the start of a
+    /// code.
+    const HEADER_LENGTH: usize = 1 + 1 + 2;
+
+    pub fn encode(&self) -> Result<String, GreacherCodeError> {
+        let (palette_index, set) = match &self.palette {
+            PaletteId::Loaded { set, index } => (*index, Some(set)),
+            PaletteId::Generated(index) => (*index, None),
+        };
+        let palette_index = u16::try_from(palette_index)
+            .map_err(|_| GreacherCodeError::PaletteTooLarge(palette_index))?;
+
+        let gene_count = gene_count(self.version);
+        let own_genes = genes(&self.genome);
+        let wild_genes = genes(&Genome::from_seed(self.genome.seed));
+        let full_genome = own_genes[..gene_count] != wild_genes[..gene_count];
+
+        let mut flags = (self.rarity as u8) << RARITY_SHIFT;
+        if self.body_type == GreacherBodyType::Wings {
+            flags |= FLAG_WINGS;
+        }
+        if full_genome {
+            flags |= FLAG_FULL_GENOME;
+        }
+        if set.is_none() {
+            flags |= FLAG_GENERATED_PALETTE;
+        }
+
+        let mut bytes = vec![self.version as u8, flags];
+        bytes.extend_from_slice(&palette_index.to_be_bytes());
+        if let Some(set) = set {
+            write_name(&mut bytes, set)?;
+        }
+        write_name(&mut bytes, &self.language)?;
+        bytes.extend_from_slice(&self.genome.seed.to_be_bytes());
+
+        if full_genome {
+            for gene in &own_genes[..gene_count] {
+                bytes.extend_from_slice(&gene.to_be_bytes());
+            }
+        }
+
+        bytes.extend_from_slice(&crc16(&bytes).to_be_bytes());
+
+        let encoded = to_base32(&bytes);
+
+        Ok(encoded
+            .as_bytes()
+            .chunks(GROUP_LENGTH)
+            .map(|group| std::str::from_utf8(group).unwrap())
+            .collect::<Vec<_>>()
+            .join("-"))
+    }
+
+    /// Reads a code back, ignoring case and dashes. Commonly confused letters
+    /// are read as the digits they look like.
+    pub fn decode(code: &str) -> Result<GreacherCode, GreacherCodeError> {
+        let bytes = from_base32(code)?;
+
+        let wrong_length =
+            || GreacherCodeError::WrongLength(code.chars().filter(|c| *c != '-').count());
+
+        if bytes.len() < Self::HEADER_LENGTH + 2 {
+            return Err(wrong_length());
+        }
+
+        let (payload, checksum) = bytes.split_at(bytes.len() - 2);
+
+        if crc16(payload).to_be_bytes() != checksum {
+            return Err(GreacherCodeError::ChecksumMismatch);
+        }
+
+        let version = GeneratorVersion::from_u8(payload[0])
+            .ok_or(GreacherCodeError::UnsupportedVersion(payload[0]))?;
+
+        let flags = payload[1];
+        let known_flags = FLAG_WINGS | FLAG_FULL_GENOME | RARITY_MASK | FLAG_GENERATED_PALETTE;
+        if flags & !known_flags != 0 {
+            return Err(GreacherCodeError::UnknownFlags(flags));
+        }
+
+        let palette_index = u16::from_be_bytes([payload[2], payload[3]]) as usize;
+        let mut rest = &payload[Self::HEADER_LENGTH..];
+        let mut name = || {
+            let name = read_name(&mut rest).ok_or_else(wrong_length)?;
+            String::from_utf8(name.to_vec()).map_err(|_| GreacherCodeError::InvalidName)
+        };
+
+        let palette = if flags & FLAG_GENERATED_PALETTE != 0 {
+            PaletteId::Generated(palette_index)
+        } else {
+            PaletteId::Loaded {
+                set: name()?,
+                index: palette_index,
+            }
+        };
+        let language = name()?;
+
+        let full_genome = flags & FLAG_FULL_GENOME != 0;
+        let expected_words = if full_genome {
+            1 + gene_count(version)
+        } else {
+            1
+        };
+        if rest.len() != expected_words * 8 {
+            return Err(wrong_length());
+        }
+
+        let mut words = rest
+            .chunks(8)
+            .map(|word| u64::from_be_bytes(word.try_into().unwrap()));
+
+        let seed = words.next().unwrap();
+        // Genes added after the code's version don't affect how it's generated,
+        // so they're rolled from the seed like a wild greacher's.
+        let mut genome = Genome::from_seed(seed);
+        if full_genome {
+            for (gene, word) in genes_mut(&mut genome).into_iter().zip(words) {
+                *gene = word;
+            }
+        }
+
+        Ok(GreacherCode {
+            version,
+            genome,
+            palette,
+            language,
+            body_type: if flags & FLAG_WINGS != 0 {
+                GreacherBodyType::Wings
+            } else {
+                GreacherBodyType::Legs
+            },
+            rarity: GreacherRarity::ALL[((flags & RARITY_MASK) >> RARITY_SHIFT) as usize],
+        })
+    }
+}
+
+/// Writes a name as its length in bytes, followed by its UTF-8.
+fn write_name(bytes: &mut Vec<u8>, name: &str) -> Result<(), GreacherCodeError> {
+    let length =
+        u8::try_from(name.len()).map_err(|_| GreacherCodeError::NameTooLong(name.to_string()))?;
+
+    bytes.push(length);
+    bytes.extend_from_slice(name.as_bytes());
+
+    Ok(())
+}
+
+/// Takes a name written by [`write_name`] off the start of `bytes`, if it's
+/// all there.
+fn read_name<'a>(bytes: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let (&length, rest) = bytes.split_first()?;
+
+    if rest.len() < length as usize {
+        return None;
+    }
+
+    let (name, rest) = rest.split_at(length as usize);
+    *bytes = rest;
+
+    Some(name)
+}
+
+/// Number of genes besides the seed the genomes of a version have. New genes
+/// are always added at the end.
+fn gene_count(version: GeneratorVersion) -> usize {
+    match version {
+        GeneratorVersion::V1 => 10,
+        GeneratorVersion::V2 | GeneratorVersion::V3 => 12,
+        GeneratorVersion::V4 => 13,
+        GeneratorVersion::V5 | GeneratorVersion::V6 => 14,
+    }
+}
+
+/// Every gene but the seed, in the order they're written to a code.
+fn genes(genome: &Genome) -> [u64; 14] {
+    [
+        genome.head_size,
+        genome.head_shape,
+        genome.head_pattern,
+        genome.eye_size,
+        genome.eye_position,
+        genome.palette,
+        genome.body_type,
+        genome.stats,
+        genome.name_start,
+        genome.name_end,
+        genome.rarity,
+        genome.rare_traits,
+        genome.body_shape,
+        genome.size,
+    ]
+}
+
+fn genes_mut(genome: &mut Genome) -> [&mut u64; 14] {
+    [
+        &mut genome.head_size,
+        &mut genome.head_shape,
+        &mut genome.head_pattern,
+        &mut genome.eye_size,
+        &mut genome.eye_position,
+        &mut genome.palette,
+        &mut genome.body_type,
+        &mut genome.stats,
+        &mut genome.name_start,
+        &mut genome.name_end,
+        &mut genome.rarity,
+        &mut genome.rare_traits,
+        &mut genome.body_shape,
+        &mut genome.size,
+    ]
+}
+
+/// CRC-16/CCITT-FALSE, which catches every single typo and swapped pair of
+/// characters.
+fn crc16(bytes: &[u8]) -> u16 {
+    let mut crc = 0xffffu16;
+
+    for byte in bytes {
+        crc ^= (*byte as u16) << 8;
+
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+
+    crc
+}
+
+fn to_base32(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(ALPHABET[(buffer >> bits) as usize & 31] as char);
+        }
+    }
+
+    if bits > 0 {
+        encoded.push(ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+    }
+
+    encoded
+}
+
+fn from_base32(code: &str) -> Result<Vec<u8>, GreacherCodeError> {
+    let mut bytes = vec![];
+    let mut buffer = 0u32;
+    let mut bits = 0;
+
+    for c in code.trim().chars().filter(|c| *c != '-') {
+        let value = match c.to_ascii_uppercase() {
+            'O' => 0,
+            'I' | 'L' => 1,
+            upper => ALPHABET
+                .iter()
+                .position(|letter| *letter as char == upper)
+                .ok_or(GreacherCodeError::InvalidCharacter(c))? as u32,
+        };
+
+        buffer = (buffer << 5) | value;
+        bits += 5;
+
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    // Padding bits are always written as zeroes, so anything else is a typo the
+    // checksum wouldn't see.
+    if buffer & ((1 << bits) - 1) != 0 {
+        return Err(GreacherCodeError::ChecksumMismatch);
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wild(seed: u64) -> GreacherCode {
+        GreacherCode {
+            version: GeneratorVersion::LATEST,
+            genome: Genome::from_seed(seed),
+            palette: PaletteId::Loaded {
+                set: "palette.png".to_string(),
+                index: 3,
+            },
+            language: "Greachish".to_string(),
+            body_type: GreacherBodyType::Wings,
+            rarity: GreacherRarity::ALL[seed as usize % GreacherRarity::ALL.len()],
+        }
+    }
+
+    fn bred(seed: u64, version: GeneratorVersion) -> GreacherCode {
+        let mut code = wild(seed);
+        code.version = version;
+        code.body_type = GreacherBodyType::Legs;
+        code.genome.head_shape ^= 1;
+        code.genome.name_end = !code.genome.name_end;
+        code.palette = PaletteId::Generated(5);
+        code
+    }
+
+    /// How many characters a code of `bytes` bytes takes, without dashes.
+    fn characters(bytes: usize) -> usize {
+        (bytes * 8).div_ceil(5)
+    }
+
+    /// Swaps the character at `index` of a written out code for `with`.
+    fn replace(code: &str, index: usize, with: char) -> String {
+        code.chars()
+            .enumerate()
+            .map(|(i, c)| if i == index { with } else { c })
+            .collect()
+    }
+
+    #[test]
+    fn short_codes_round_trip() {
+        for seed in [0, 1, 42, u64::MAX] {
+            let code = wild(seed);
+            let encoded = code.encode().unwrap();
+
+            // The header, both names with their lengths, the seed and the
+            // checksum.
+            let bytes = 4 + 12 + 10 + 8 + 2;
+            assert_eq!(
+                encoded.chars().filter(|c| *c != '-').count(),
+                characters(bytes)
+            );
+            assert_eq!(GreacherCode::decode(&encoded), Ok(code));
+        }
+    }
+
+    #[test]
+    fn full_codes_round_trip() {
+        for version in GeneratorVersion::ALL {
+            for seed in [0, 7, u64::MAX] {
+                let code = bred(seed, version);
+                let encoded = code.encode().unwrap();
+
+                assert!(encoded.len() > wild(seed).encode().unwrap().len());
+                assert_eq!(GreacherCode::decode(&encoded), Ok(code));
+            }
+        }
+    }
+
+    #[test]
+    fn bad_checksums_are_rejected() {
+        let encoded = wild(42).encode().unwrap();
+
+        // Typos in the last character may land in the padding instead, which is
+        // reported the same way.
+        for index in (0..encoded.len()).filter(|i| encoded.as_bytes()[*i] != b'-') {
+            let original = encoded.as_bytes()[index];
+            let typo = if original == b'X' { 'Y' } else { 'X' };
+            let typo = replace(&encoded, index, typo);
+            assert_eq!(
+                GreacherCode::decode(&typo),
+                Err(GreacherCodeError::ChecksumMismatch),
+                "{} was accepted",
+                typo
+            );
+        }
+    }
+
+    #[test]
+    fn bad_characters_are_rejected() {
+        let encoded = wild(42).encode().unwrap();
+
+        for bad in ['U', 'u', '!', ' ', 'é'] {
+            assert_eq!(
+                GreacherCode::decode(&replace(&encoded, 2, bad)),
+                Err(GreacherCodeError::InvalidCharacter(bad))
+            );
+        }
+    }
+
+    #[test]
+    fn padding_bits_must_be_zero() {
+        let encoded = wild(42).encode().unwrap();
+        let last = encoded.chars().last().unwrap();
+        let value = ALPHABET.iter().position(|c| *c as char == last).unwrap();
+
+        // 36 bytes take 58 characters, leaving the last two bits as padding.
+        assert_eq!(characters(36), 58);
+        for padding in 1..4 {
+            let padded = ALPHABET[value | padding] as char;
+            assert_eq!(
+                GreacherCode::decode(&replace(&encoded, encoded.len() - 1, padded)),
+                Err(GreacherCodeError::ChecksumMismatch)
+            );
+        }
+    }
+
+    #[test]
+    fn lookalike_letters_read_as_digits() {
+        assert_eq!(from_base32("OIL"), from_base32("011"));
+        assert_eq!(from_base32("oil"), from_base32("011"));
+
+        for seed in 0..50 {
+            let code = wild(seed);
+            let misread: String = code
+                .encode()
+                .unwrap()
+                .chars()
+                .map(|c| match c {
+                    '0' => 'O',
+                    '1' if seed % 2 == 0 => 'I',
+                    '1' => 'l',
+                    c => c.to_ascii_lowercase(),
+                })
+                .collect();
+
+            assert_eq!(GreacherCode::decode(&misread), Ok(code));
+        }
+    }
+
+    #[test]
+    fn palettes_past_the_field_are_rejected() {
+        let mut code = wild(42);
+
+        code.palette = PaletteId::Generated(u16::MAX as usize);
+        assert_eq!(
+            GreacherCode::decode(&code.encode().unwrap()),
+            Ok(code.clone())
+        );
+
+        code.palette = PaletteId::Loaded {
+            set: "Big".to_string(),
+            index: u16::MAX as usize + 1,
+        };
+        assert_eq!(
+            code.encode(),
+            Err(GreacherCodeError::PaletteTooLarge(u16::MAX as usize + 1))
+        );
+    }
+
+    #[test]
+    fn names_round_trip_up_to_255_bytes() {
+        let mut code = wild(42);
+
+        code.language = "é".repeat(127) + "e";
+        code.palette = PaletteId::Loaded {
+            set: String::new(),
+            index: 0,
+        };
+        assert_eq!(
+            GreacherCode::decode(&code.encode().unwrap()),
+            Ok(code.clone())
+        );
+
+        code.language.push('e');
+        assert_eq!(
+            code.encode(),
+            Err(GreacherCodeError::NameTooLong(code.language.clone()))
+        );
+    }
+
+    #[test]
+    fn cut_off_names_are_the_wrong_length() {
+        // The language name claims more bytes than the code has left.
+        let mut bytes = vec![
+            GeneratorVersion::LATEST as u8,
+            FLAG_GENERATED_PALETTE,
+            0,
+            0,
+            200,
+        ];
+        bytes.extend_from_slice(b"Greachish");
+        bytes.extend_from_slice(&crc16(&bytes).to_be_bytes());
+
+        let code = to_base32(&bytes);
+        assert_eq!(
+            GreacherCode::decode(&code),
+            Err(GreacherCodeError::WrongLength(code.len()))
+        );
+    }
+}
@@ -1,52 +1,323 @@
+use std::ops::Range;
+
 use bevy::prelude::*;
+use bevy_rapier2d::prelude::{CoefficientCombineRule, Damping, Friction};
 use bitmask_enum::bitmask;
-use rand::{random, rngs::SmallRng, SeedableRng, Rng};
+use rand::{random, rngs::SmallRng, thread_rng, Rng};
 
-use crate::{color::{GreacherColorPalette, GreacherPalettes}, util::SliceExt};
+use crate::{
+    color::{apply_palette, GreacherColorPalette, GreacherPalettes, PaletteId},
+    index_keys::IndexKeys,
+    util::SliceExt,
+};
 
-use super::gen::{generate_greacher_head_texture, generate_greacher_name};
+use super::{
+    code::{GreacherCode, GreacherCodeError},
+    gen::{
+        create_head_image, generate_greacher_body_shape, generate_greacher_body_texture,
+        generate_greacher_body_type, generate_greacher_head_texture, generate_greacher_name,
+        generate_greacher_size, generate_greacher_stats, GeneratorVersion, HeadFrame,
+        BODY_ATLAS_COLUMNS, BODY_ATLAS_ROWS, BODY_FRAME_SIZE, GREACHER_CANVAS_SIZE,
+    },
+    genome::{gene_rng, Genome},
+    names::NameLanguage,
+    rarity::{GreacherRarity, GreacherTraits, RarityWeights, TITLES},
+};
 
 #[derive(Component, Clone)]
 pub struct Greacher {
-    pub seed: u64,
+    pub genome: Genome,
+    /// Generators the greacher was made with, which stay the same after
+    /// they're updated.
+    pub version: GeneratorVersion,
     pub name: String,
+    /// Name of the language the name was made up in.
+    pub language: String,
     pub generated: GreacherParts,
     pub body_type: GreacherBodyType,
-    pub palette: (usize, GreacherColorPalette),
+    pub palette: (PaletteId, GreacherColorPalette),
+    /// Genomes of the two greachers this one was bred from.
+    pub parents: Option<(Genome, Genome)>,
+    pub rarity: GreacherRarity,
+    pub traits: GreacherTraits,
+    /// How many times bigger than usual the whole greacher is.
+    pub size: f32,
 }
 
-#[derive(Clone, Copy, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum GreacherBodyType {
     Legs,
     Wings,
 }
 
+impl GreacherBodyType {
+    /// The hand drawn sheet of the body, if it isn't generated by the given
+    /// version.
+    pub fn sheet_path(&self, version: GeneratorVersion) -> Option<&'static str> {
+        match self {
+            GreacherBodyType::Legs if version.has_generated_legs() => None,
+            GreacherBodyType::Legs => Some("indexed/legs.png"),
+            GreacherBodyType::Wings => Some("indexed/wings.png"),
+        }
+    }
+
+    /// Frame size, columns and rows of the body sprite sheet.
+    pub fn sheet_layout(&self) -> (Vec2, usize, usize) {
+        match self {
+            GreacherBodyType::Legs => (
+                Vec2::new(BODY_FRAME_SIZE.0 as f32, BODY_FRAME_SIZE.1 as f32),
+                BODY_ATLAS_COLUMNS,
+                BODY_ATLAS_ROWS,
+            ),
+            GreacherBodyType::Wings => (Vec2::new(20.0, 10.0), 5, 1),
+        }
+    }
+
+    /// Where the body sprite sits relative to a head `head_size` pixels tall.
+    pub fn body_offset(&self, head_size: f32) -> Vec3 {
+        match self {
+            GreacherBodyType::Legs => Vec3::new(0., -(head_size / 2. + 2.), 0.),
+            GreacherBodyType::Wings => Vec3::new(0., 0., -0.001),
+        }
+    }
+
+    /// Wings grow along with the head so they still stick out of it.
+    pub fn body_scale(&self, head_size: f32) -> Vec3 {
+        match self {
+            GreacherBodyType::Legs => Vec3::ONE,
+            GreacherBodyType::Wings => Vec3::splat(head_size / GREACHER_CANVAS_SIZE as f32),
+        }
+    }
+
+    pub fn damping(&self) -> Damping {
+        match self {
+            GreacherBodyType::Legs => Damping {
+                linear_damping: 0.9,
+                angular_damping: 1.0,
+            },
+            GreacherBodyType::Wings => Damping {
+                linear_damping: 0.3,
+                angular_damping: 0.5,
+            },
+        }
+    }
+
+    pub fn friction(&self) -> Friction {
+        match self {
+            GreacherBodyType::Legs => Friction::default(),
+            GreacherBodyType::Wings => Friction {
+                coefficient: 0.,
+                combine_rule: CoefficientCombineRule::Min,
+            },
+        }
+    }
+}
+
+/// Vertical bob of a flying greacher, in pixels of its head. It only moves
+/// the sprites, so the bob doesn't fight the physics.
+#[derive(Component, Default)]
+pub struct GreacherHover {
+    pub phase: f32,
+    /// Offset applied on the last frame, so it can be undone on the next one.
+    pub offset: f32,
+    /// Height of the head sprite, which is bobbed through its anchor.
+    pub head_size: f32,
+}
+
+impl GreacherHover {
+    pub const AMPLITUDE: f32 = 1.5;
+    pub const FREQUENCY: f32 = 4.0;
+}
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct GreacherStats {
+    /// Top speed, in pixels per second.
+    pub speed: f32,
+    /// How fast the greacher gets up to speed, in pixels per second squared.
+    pub acceleration: f32,
+    pub max_health: f32,
+    pub strength: f32,
+    /// Hunger gained per second.
+    pub hunger_rate: f32,
+    /// How far away the greacher notices things, in pixels.
+    pub sight_radius: f32,
+}
+
 #[bitmask(u8)]
 pub enum GreacherParts {
     Head,
+    /// Body type, shape and size. Size also scales the sprite, collider and
+    /// mass, and slows or quickens the stats rolled after it.
     Body,
     Stats,
     Name,
+    Palette,
 }
 
 impl Greacher {
     pub const SIZE: f32 = 6.0;
     pub const STILL_EPSILON: f32 = 1.;
+    /// Share of its acceleration a greacher heads for the cursor with while
+    /// it's beyond its sight radius.
+    pub const UNSEEN_ACCELERATION: f32 = 0.25;
+    /// Mass of a greacher of the usual size, about what rapier would give its
+    /// collider.
+    pub const MASS: f32 = 80.0;
+
+    pub fn new(
+        head_texture: &mut Image,
+        palettes: &GreacherPalettes,
+        language: &NameLanguage,
+        rarity_weights: &RarityWeights,
+    ) -> Greacher {
+        Greacher::from_genome(
+            Genome::from_seed(random()),
+            head_texture,
+            palettes,
+            language,
+            rarity_weights,
+        )
+    }
+
+    pub fn from_genome(
+        genome: Genome,
+        head_texture: &mut Image,
+        palettes: &GreacherPalettes,
+        language: &NameLanguage,
+        rarity_weights: &RarityWeights,
+    ) -> Greacher {
+        Greacher::from_versioned_genome(
+            genome,
+            GeneratorVersion::LATEST,
+            head_texture,
+            palettes,
+            language,
+            rarity_weights,
+        )
+    }
+
+    /// Generates the greacher the way the given version of the generators
+    /// did.
+    pub fn from_versioned_genome(
+        genome: Genome,
+        version: GeneratorVersion,
+        head_texture: &mut Image,
+        palettes: &GreacherPalettes,
+        language: &NameLanguage,
+        rarity_weights: &RarityWeights,
+    ) -> Greacher {
+        let mut greacher = Greacher::ungenerated(genome, version);
 
-    pub fn new(head_texture: &mut Image, palettes: &GreacherPalettes) -> Greacher {
-        let generated_flags = GreacherParts::none();
+        greacher.generate(head_texture, palettes, language, rarity_weights);
 
-        let mut greacher = Greacher {
-            seed: random(),
+        greacher
+    }
+
+    fn ungenerated(genome: Genome, version: GeneratorVersion) -> Greacher {
+        Greacher {
+            genome,
+            version,
             name: String::new(),
-            generated: generated_flags,
+            language: String::new(),
+            generated: GreacherParts::none(),
             body_type: GreacherBodyType::Legs,
-            palette: (0, GreacherColorPalette::default())
-        };
+            palette: (PaletteId::Generated(0), GreacherColorPalette::default()),
+            parents: None,
+            rarity: GreacherRarity::Common,
+            traits: GreacherTraits::none(),
+            size: 1.,
+        }
+    }
 
-        greacher.generate(head_texture, palettes);
+    pub fn breed(
+        a: &Greacher,
+        b: &Greacher,
+        mutation_rate: f32,
+        head_texture: &mut Image,
+        palettes: &GreacherPalettes,
+        language: &NameLanguage,
+        rarity_weights: &RarityWeights,
+    ) -> Greacher {
+        let genome = Genome::crossover(&a.genome, &b.genome, mutation_rate, &mut thread_rng());
 
-        greacher
+        let mut child =
+            Greacher::from_genome(genome, head_texture, palettes, language, rarity_weights);
+        child.parents = Some((a.genome, b.genome));
+
+        // An inherited palette is taken from the parent as it is, rather than
+        // rolled again from the gene, in case the palettes changed since the
+        // parent was made. Mutated ones keep the palette they rolled.
+        let parent = [a, b]
+            .into_iter()
+            .find(|parent| parent.genome.palette == genome.palette);
+
+        if let Some(parent) = parent {
+            if parent.palette != child.palette {
+                child.palette = parent.palette.clone();
+                let base_canvas_size = child.base_canvas_size(head_texture);
+                child.generate_head(head_texture, base_canvas_size);
+            }
+        }
+
+        child
+    }
+
+    /// Rebuilds the greacher a code was made from. The palettes and language
+    /// have to be the ones it was made with, or it would come out different.
+    pub fn from_code(
+        code: &str,
+        head_texture: &mut Image,
+        palettes: &GreacherPalettes,
+        language: &NameLanguage,
+    ) -> Result<Greacher, GreacherCodeError> {
+        let code = GreacherCode::decode(code)?;
+
+        if code.language != language.name {
+            return Err(GreacherCodeError::UnknownLanguage(code.language));
+        }
+
+        let palette_index = palettes
+            .find(&code.palette)
+            .ok_or_else(|| match &code.palette {
+                PaletteId::Loaded { set, .. } if *set != palettes.set => {
+                    GreacherCodeError::UnknownPaletteSet(set.clone())
+                }
+                PaletteId::Loaded { index, .. } => GreacherCodeError::PaletteOutOfRange {
+                    index: *index,
+                    palettes: palettes.loaded,
+                },
+                PaletteId::Generated(index) => GreacherCodeError::PaletteOutOfRange {
+                    index: *index,
+                    palettes: GreacherPalettes::GENERATED,
+                },
+            })?;
+
+        let mut greacher = Greacher::ungenerated(code.genome, code.version);
+        greacher.generate_as(code.rarity, head_texture, palettes, language);
+
+        // The palette gene picks from whatever palettes are loaded, so the
+        // code's palette wins in case they're different ones.
+        if greacher.palette.0 != code.palette {
+            greacher.palette = (code.palette, palettes.palettes[palette_index].clone());
+            let base_canvas_size = greacher.base_canvas_size(head_texture);
+            greacher.generate_head(head_texture, base_canvas_size);
+        }
+        greacher.body_type = code.body_type;
+
+        Ok(greacher)
+    }
+
+    /// A short text code other players can rebuild this greacher from.
+    pub fn to_code(&self) -> Result<String, GreacherCodeError> {
+        GreacherCode {
+            version: self.version,
+            genome: self.genome,
+            palette: self.palette.0.clone(),
+            language: self.language.clone(),
+            body_type: self.body_type,
+            rarity: self.rarity,
+        }
+        .encode()
     }
 
     pub fn mark_as_generated(&mut self, category: GreacherParts) {
@@ -57,22 +328,202 @@ impl Greacher {
         self.generated.contains(category)
     }
 
-    pub fn generate(&mut self, head_texture: &mut Image, palettes: &GreacherPalettes) {
-        let mut rng = SmallRng::seed_from_u64(self.seed);
+    pub fn generate(
+        &mut self,
+        head_texture: &mut Image,
+        palettes: &GreacherPalettes,
+        language: &NameLanguage,
+        rarity_weights: &RarityWeights,
+    ) {
+        let rarity = if self.version.has_rarity() {
+            rarity_weights.roll(&mut gene_rng(self.genome.rarity))
+        } else {
+            GreacherRarity::Common
+        };
+
+        self.generate_as(rarity, head_texture, palettes, language);
+    }
+
+    /// Generates the greacher as the given tier, whatever its rarity gene says.
+    fn generate_as(
+        &mut self,
+        rarity: GreacherRarity,
+        head_texture: &mut Image,
+        palettes: &GreacherPalettes,
+        language: &NameLanguage,
+    ) {
+        let base_canvas_size = self.base_canvas_size(head_texture);
+
+        self.rarity = rarity;
+        self.traits = self.roll_traits().0;
+
+        self.generate_name(language);
+        self.generate_palette(palettes);
+        self.generate_body_type();
+        self.generate_head(head_texture, base_canvas_size);
+    }
+
+    /// Rerolls only the given parts, keeping everything else about the
+    /// greacher, rarity included. Stats and bodies are generated when the
+    /// greacher is spawned, so those are just marked as not generated.
+    pub fn regenerate_parts(
+        &mut self,
+        parts: GreacherParts,
+        head_texture: &mut Image,
+        palettes: &GreacherPalettes,
+        language: &NameLanguage,
+    ) {
+        self.genome.reroll_parts(parts, &mut thread_rng());
+        self.generated &= !parts;
+
+        if parts.contains(GreacherParts::Name) {
+            self.generate_name(language);
+        }
+        if parts.contains(GreacherParts::Palette) {
+            self.generate_palette(palettes);
+        }
+        if parts.contains(GreacherParts::Body) {
+            self.generate_body_type();
+        }
+        if parts.intersects(GreacherParts::Head | GreacherParts::Palette) {
+            let base_canvas_size = self.base_canvas_size(head_texture);
+            self.generate_head(head_texture, base_canvas_size);
+        }
+    }
+
+    /// Rolls the traits of the greacher's tier. Also returns the stream they
+    /// were rolled from, which titles are picked from next.
+    fn roll_traits(&self) -> (GreacherTraits, SmallRng) {
+        let mut rng = gene_rng(self.genome.rare_traits);
+        let traits = self.rarity.roll_traits(&mut rng);
 
-        self.name = generate_greacher_name(&mut rng);
-        let palette_index = rng.gen_range(0..palettes.palettes.len());
-        self.palette = (palette_index, palettes.palettes[palette_index].clone());
-        generate_greacher_head_texture(&mut rng, head_texture, &self.palette.1);
+        (traits, rng)
+    }
+
+    fn generate_name(&mut self, language: &NameLanguage) {
+        self.name = generate_greacher_name(
+            language,
+            &mut gene_rng(self.genome.name_start),
+            &mut gene_rng(self.genome.name_end),
+        );
+        if self.traits.contains(GreacherTraits::UniquePostfix) {
+            let mut title_rng = self.roll_traits().1;
+            self.name = format!("{} {}", self.name, TITLES.random(&mut title_rng));
+        }
+        self.language = language.name.clone();
+        self.mark_as_generated(GreacherParts::Name);
+    }
+
+    /// Picks the body type, and the size which goes along with the body.
+    fn generate_body_type(&mut self) {
+        self.body_type = generate_greacher_body_type(&mut gene_rng(self.genome.body_type));
+        self.size = if self.version.has_sizes() {
+            generate_greacher_size(&mut gene_rng(self.genome.size))
+        } else {
+            1.
+        };
+    }
+
+    fn generate_palette(&mut self, palettes: &GreacherPalettes) {
+        let palette_index =
+            gene_rng(self.genome.palette).gen_range(0..palettes.available(self.version));
+        self.palette = (
+            palettes.id(palette_index),
+            palettes.palettes[palette_index].clone(),
+        );
+        self.mark_as_generated(GreacherParts::Palette);
+    }
+
+    /// Canvas size of a regular head, going by the size of this greacher's.
+    fn base_canvas_size(&self, head_texture: &Image) -> usize {
+        head_texture.texture_descriptor.size.height as usize / self.head_scale()
+    }
+
+    /// Draws the head atlas, resizing it first if the greacher's head scale
+    /// doesn't match it.
+    fn generate_head(&mut self, head_texture: &mut Image, base_canvas_size: usize) {
+        let canvas_size = base_canvas_size * self.head_scale();
+        if head_texture.texture_descriptor.size.height as usize != canvas_size {
+            *head_texture = create_head_image(canvas_size);
+        }
+        generate_greacher_head_texture(
+            &self.genome,
+            head_texture,
+            &self.palette.1,
+            self.traits,
+            self.version,
+        );
         self.mark_as_generated(GreacherParts::Head);
     }
 
-    pub fn regenerate(&mut self, head_texture: &mut Image, palettes: &GreacherPalettes) {
-        self.seed = random();
+    /// Swaps the greacher's palette for an edited version of the same one,
+    /// redrawing the head into `head_texture`. Also returns the redrawn body,
+    /// if it's generated rather than a recolored sheet.
+    pub fn recolor(
+        &mut self,
+        palette: GreacherColorPalette,
+        head_texture: &mut Image,
+    ) -> Option<Image> {
+        self.palette.1 = palette;
+
+        let base_canvas_size = self.base_canvas_size(head_texture);
+        self.generate_head(head_texture, base_canvas_size);
+
+        self.generate_body()
+    }
+
+    /// Rolls the stats, with bigger greachers slower to get going and smaller
+    /// ones quicker.
+    pub fn generate_stats(&mut self) -> GreacherStats {
+        let mut stats = generate_greacher_stats(&mut gene_rng(self.genome.stats));
+        stats.speed /= self.size;
+        stats.acceleration /= self.size;
+        self.mark_as_generated(GreacherParts::Stats);
+
+        stats
+    }
+
+    /// Draws the body atlas in the greacher's palette, unless its body type
+    /// uses a hand drawn sheet.
+    pub fn generate_body(&mut self) -> Option<Image> {
+        if self.body_type.sheet_path(self.version).is_some() {
+            return None;
+        }
+
+        let shape = generate_greacher_body_shape(&mut gene_rng(self.genome.body_shape));
+        let body = generate_greacher_body_texture(&shape);
+        self.mark_as_generated(GreacherParts::Body);
+
+        Some(apply_palette(&body, &IndexKeys::default(), &self.palette.1))
+    }
+
+    pub fn regenerate(
+        &mut self,
+        head_texture: &mut Image,
+        palettes: &GreacherPalettes,
+        language: &NameLanguage,
+        rarity_weights: &RarityWeights,
+    ) {
+        self.genome = Genome::from_seed(random());
+        self.version = GeneratorVersion::LATEST;
 
         self.generated = GreacherParts::none();
 
-        self.generate(head_texture, palettes);
+        self.generate(head_texture, palettes, language, rarity_weights);
+    }
+
+    /// Mass of the greacher's rigid body, which grows with the area it covers.
+    pub fn mass(&self) -> f32 {
+        Self::MASS * (self.size * self.head_scale() as f32).powi(2)
+    }
+
+    /// How many times larger than usual the head canvas is.
+    pub fn head_scale(&self) -> usize {
+        if self.traits.contains(GreacherTraits::DoubleHead) {
+            2
+        } else {
+            1
+        }
     }
 }
 
@@ -107,3 +558,45 @@ impl GreacherBodyAnimation {
         }
     }
 }
+
+/// Picks the frame of the head atlas, from what the greacher is up to and
+/// whether it's blinking.
+#[derive(Component)]
+pub struct GreacherHeadAnimation {
+    pub expression: HeadFrame,
+    /// Counts down to the next blink, or to the end of the current one.
+    pub blink_timer: Timer,
+    pub blinking: bool,
+}
+
+impl GreacherHeadAnimation {
+    pub const BLINK_DURATION: f32 = 0.15;
+    /// Seconds between blinks, picked anew after each one.
+    pub const BLINK_INTERVAL: Range<f32> = 1.5..6.0;
+
+    /// Speed, as a fraction of the top speed, above which chasing greachers
+    /// squint instead of gaping.
+    pub const SQUINT_SPEED: f32 = 0.75;
+
+    pub fn frame(&self) -> HeadFrame {
+        // Blinking shuts the mouth too, so gaping greachers hold off on it.
+        if self.blinking && self.expression != HeadFrame::OpenMouth {
+            HeadFrame::Blink
+        } else {
+            self.expression
+        }
+    }
+}
+
+impl Default for GreacherHeadAnimation {
+    fn default() -> Self {
+        GreacherHeadAnimation {
+            expression: HeadFrame::Idle,
+            blink_timer: Timer::from_seconds(
+                thread_rng().gen_range(GreacherHeadAnimation::BLINK_INTERVAL),
+                false,
+            ),
+            blinking: false,
+        }
+    }
+}
@@ -1,30 +1,31 @@
-use bevy::{
-    prelude::*,
-    render::{
-        camera::RenderTarget,
-        render_resource::{
-            Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
-        },
-    },
-};
+use bevy::{prelude::*, render::camera::RenderTarget};
 
 use bevy_rapier2d::prelude::*;
 
 use crate::{
     basics::components::MovementHistory,
     camera::{GameCamera, GameWorldRenderLayer},
-    color::{GreacherPalettes, IndexedImageServer},
+    color::{GreacherPalettes, PalettesReloaded},
+    palette_swap::{IndexedSprites, PaletteSwapMaterial},
     states::AppState,
     util::rand_range_f32,
 };
 
 use super::{
-    behavior::{animate_greacher_body, go_towards_mouse, limit_greacher_velocity, set_z},
-    components::{Greacher, GreacherBodyAnimation},
+    behavior::{
+        animate_greacher_body, animate_greacher_head, go_towards_mouse, hover_flying_greachers,
+        limit_greacher_velocity, set_z, shimmer_greachers,
+    },
+    breeding::{breed_greachers, pick_parents_near_mouse, BreedGreachers, BreedingConfig},
+    components::{
+        Greacher, GreacherBodyAnimation, GreacherBodyType, GreacherHeadAnimation, GreacherHover,
+    },
+    gen::{create_head_image, HeadFrame, GREACHER_CANVAS_SIZE},
+    label::{spawn_greacher_label, update_greacher_label},
+    names::{NameLanguage, NameLanguageLoader, NameLanguages, NameRegistry},
+    rarity::{GreacherShimmer, GreacherTraits, RarityWeights},
 };
 
-struct GreetTimer(Timer);
-
 #[derive(Deref, DerefMut)]
 pub struct WorldMouse(Vec2);
 
@@ -34,116 +35,219 @@ pub struct GreacherGamePlugin;
 
 impl Plugin for GreacherGamePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(GreetTimer(Timer::from_seconds(2.0, true)))
-            .insert_resource(GreacherHeadImageTemplate(Image {
-                texture_descriptor: TextureDescriptor {
-                    label: None,
-                    size: Extent3d {
-                        width: 10,
-                        height: 10,
-                        ..Default::default()
-                    },
-                    mip_level_count: 1,
-                    sample_count: 1,
-                    dimension: TextureDimension::D2,
-                    format: TextureFormat::Rgba8Unorm,
-                    usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
-                },
-                ..Default::default()
-            }))
-            .insert_resource(WorldMouse(Vec2::ZERO))
-            .add_system_set(SystemSet::on_enter(AppState::InGame).with_system(setup))
-            .add_system_to_stage(CoreStage::PreUpdate, world_cursor_pos)
-            .add_system_to_stage(CoreStage::PreUpdate, MovementHistory::set_last_position)
-            .add_system_to_stage(CoreStage::PostUpdate, MovementHistory::set_actually_moved)
-            .add_system_set(
-                SystemSet::on_update(AppState::InGame)
-                    .with_system(go_towards_mouse)
-                    .with_system(set_z)
-                    .with_system(limit_greacher_velocity)
-                    .with_system(animate_greacher_body),
-            );
+        app.insert_resource(GreacherHeadImageTemplate(create_head_image(
+            GREACHER_CANVAS_SIZE,
+        )))
+        .insert_resource(WorldMouse(Vec2::ZERO))
+        .insert_resource(BreedingConfig::default())
+        .insert_resource(RarityWeights::default())
+        .add_event::<BreedGreachers>()
+        .add_asset::<NameLanguage>()
+        .init_asset_loader::<NameLanguageLoader>()
+        .init_resource::<NameLanguages>()
+        .insert_resource(NameRegistry::default())
+        .add_system(NameLanguages::cycle_language)
+        .add_system_to_stage(CoreStage::PostUpdate, NameRegistry::release_removed)
+        .add_system_set(
+            SystemSet::on_enter(AppState::InGame)
+                .with_system(setup)
+                .with_system(spawn_greacher_label),
+        )
+        .add_system_to_stage(CoreStage::PreUpdate, world_cursor_pos)
+        .add_system_to_stage(CoreStage::PreUpdate, MovementHistory::set_last_position)
+        .add_system_to_stage(CoreStage::PostUpdate, MovementHistory::set_actually_moved)
+        .add_system_set(
+            SystemSet::on_update(AppState::InGame)
+                .with_system(go_towards_mouse)
+                .with_system(set_z)
+                .with_system(limit_greacher_velocity)
+                .with_system(animate_greacher_body)
+                .with_system(animate_greacher_head)
+                .with_system(hover_flying_greachers)
+                .with_system(shimmer_greachers)
+                .with_system(update_greacher_label)
+                .with_system(pick_parents_near_mouse)
+                .with_system(share_greacher_near_mouse)
+                .with_system(recolor_reloaded_greachers)
+                .with_system(breed_greachers.after(pick_parents_near_mouse)),
+        );
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn setup(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    indexed_server: Res<IndexedImageServer>,
+    mut indexed_sprites: IndexedSprites,
     mut images: ResMut<Assets<Image>>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     greacher_palettes: Res<GreacherPalettes>,
     head_template: Res<GreacherHeadImageTemplate>,
     game_world_render_layer: Res<GameWorldRenderLayer>,
+    name_languages: Res<NameLanguages>,
+    languages: Res<Assets<NameLanguage>>,
+    mut name_registry: ResMut<NameRegistry>,
+    rarity_weights: Res<RarityWeights>,
 ) {
     for _ in 0..1000 {
         create_new_greacher(
             &mut commands,
             &asset_server,
-            &indexed_server,
+            &mut indexed_sprites,
             &mut images,
             &mut texture_atlases,
             &greacher_palettes,
             &head_template,
             Vec2::new(rand_range_f32(-100., 100.), rand_range_f32(-100., 100.)),
             &game_world_render_layer,
+            name_languages.current(&languages),
+            &mut name_registry,
+            &rarity_weights,
         );
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn create_new_greacher(
     commands: &mut Commands,
     asset_server: &Res<AssetServer>,
-    indexed_server: &Res<IndexedImageServer>,
+    indexed_sprites: &mut IndexedSprites,
     images: &mut ResMut<Assets<Image>>,
     texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
     greacher_palettes: &Res<GreacherPalettes>,
     head_template: &GreacherHeadImageTemplate,
     position: Vec2,
     game_world_render_layer: &Res<GameWorldRenderLayer>,
+    language: &NameLanguage,
+    name_registry: &mut NameRegistry,
+    rarity_weights: &RarityWeights,
 ) {
     let mut tex = head_template.0.clone();
 
-    let greacher = Greacher::new(&mut tex, greacher_palettes);
+    let greacher = Greacher::new(&mut tex, greacher_palettes, language, rarity_weights);
+
+    spawn_greacher(
+        commands,
+        asset_server,
+        indexed_sprites,
+        images,
+        texture_atlases,
+        greacher,
+        tex,
+        position,
+        game_world_render_layer,
+        name_registry,
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_greacher(
+    commands: &mut Commands,
+    asset_server: &Res<AssetServer>,
+    indexed_sprites: &mut IndexedSprites,
+    images: &mut ResMut<Assets<Image>>,
+    texture_atlases: &mut ResMut<Assets<TextureAtlas>>,
+    mut greacher: Greacher,
+    tex: Image,
+    position: Vec2,
+    game_world_render_layer: &Res<GameWorldRenderLayer>,
+    name_registry: &mut NameRegistry,
+) {
+    let stats = greacher.generate_stats();
+    let body = greacher.generate_body();
 
     let greacher_body_type = greacher.body_type;
+    let head_size = tex.texture_descriptor.size.height as f32;
 
-    let handle = images.add(tex);
+    let head_atlas = TextureAtlas::from_grid(
+        images.add(tex),
+        Vec2::splat(head_size),
+        HeadFrame::ALL.len(),
+        1,
+    );
 
-    let parent = commands
-        .spawn_bundle(SpriteBundle {
-            texture: handle,
+    let parent = commands.spawn().id();
+    greacher.name = name_registry.claim(parent, &greacher.name);
+
+    commands
+        .entity(parent)
+        .insert_bundle(SpriteSheetBundle {
+            texture_atlas: texture_atlases.add(head_atlas),
+            // Scaling the parent scales the body and collider along with the
+            // head.
             transform: Transform::from_translation(Vec3::new(
                 position.x,
                 position.y,
-                (greacher.seed % 1_000_000) as f32 / 1_000_000.,
-            )),
+                (greacher.genome.seed % 1_000_000) as f32 / 1_000_000.,
+            ))
+            .with_scale(Vec3::splat(greacher.size)),
             ..Default::default()
         })
         .insert(greacher.clone())
+        .insert(stats)
+        .insert(GreacherHeadAnimation::default())
         .insert(MovementHistory::default())
         .insert(Velocity::default())
-        .insert(Collider::ball(5.))
+        .insert(Collider::ball(head_size / 2.))
+        .insert(ColliderMassProperties::Mass(greacher.mass()))
         .insert(RigidBody::Dynamic)
         .insert(GravityScale(0.))
-        .insert(Damping {
-            linear_damping: 0.9,
-            angular_damping: 1.0,
-        })
+        .insert(greacher_body_type.damping())
+        .insert(greacher_body_type.friction())
         .insert(LockedAxes::ROTATION_LOCKED)
-        .insert(game_world_render_layer.0)
-        .id();
-
-    let texture_handle = indexed_server.get(&asset_server.load("indexed/legs.png"), greacher.palette.0);
-    let texture_atlas = TextureAtlas::from_grid(texture_handle, Vec2::new(8.0, 6.0), 8, 2);
-    let texture_atlas_handle = texture_atlases.add(texture_atlas);
+        .insert(game_world_render_layer.0);
 
-    let child = commands
-        .spawn_bundle(SpriteSheetBundle {
-            texture_atlas: texture_atlas_handle,
-            transform: Transform::from_xyz(0., -7., 0.),
+    if greacher_body_type == GreacherBodyType::Wings {
+        commands.entity(parent).insert(GreacherHover {
+            phase: rand_range_f32(0., std::f32::consts::TAU),
+            head_size,
             ..Default::default()
-        })
+        });
+    }
+
+    if greacher.traits.contains(GreacherTraits::Shimmering) {
+        commands.entity(parent).insert(GreacherShimmer {
+            phase: rand_range_f32(0., 360.),
+        });
+    }
+
+    let (tile_size, columns, rows) = greacher_body_type.sheet_layout();
+    let transform = Transform::from_translation(greacher_body_type.body_offset(head_size))
+        .with_scale(greacher_body_type.body_scale(head_size));
+
+    let mut child = commands.spawn();
+
+    match (body, greacher_body_type.sheet_path(greacher.version)) {
+        (Some(body), _) => {
+            let texture_atlas = TextureAtlas::from_grid(images.add(body), tile_size, columns, rows);
+
+            child.insert_bundle(SpriteSheetBundle {
+                texture_atlas: texture_atlases.add(texture_atlas),
+                transform,
+                ..Default::default()
+            });
+        }
+        (None, Some(sheet_path)) => {
+            let texture_atlas =
+                TextureAtlas::from_grid(asset_server.load(sheet_path), tile_size, columns, rows);
+
+            let result = indexed_sprites.insert_sheet(
+                &mut child,
+                texture_atlas,
+                &greacher.palette.1,
+                transform,
+                texture_atlases,
+                images,
+            );
+
+            if let Err(why) = result {
+                error!("Couldn't draw the body of {}: {}", greacher.name, why);
+            }
+        }
+        (None, None) => unreachable!("Bodies without a sheet are always generated"),
+    }
+
+    let child = child
         .insert(GreacherBodyAnimation::new(&greacher_body_type))
         .insert(game_world_render_layer.0)
         .id();
@@ -151,6 +255,96 @@ fn create_new_greacher(
     commands.entity(parent).push_children(&[child]);
 }
 
+/// Recolors live greachers in place when the palettes they were colored
+/// with are reloaded. Greachers colored with other palettes, or whose
+/// palette was removed, keep their colors.
+#[allow(clippy::too_many_arguments)]
+fn recolor_reloaded_greachers(
+    mut reloaded: EventReader<PalettesReloaded>,
+    greacher_palettes: Res<GreacherPalettes>,
+    mut greachers: Query<(&mut Greacher, &Handle<TextureAtlas>, &Children)>,
+    body_atlases: Query<&Handle<TextureAtlas>, With<GreacherBodyAnimation>>,
+    body_materials: Query<&Handle<PaletteSwapMaterial>, With<GreacherBodyAnimation>>,
+    texture_atlases: Res<Assets<TextureAtlas>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: Option<ResMut<Assets<PaletteSwapMaterial>>>,
+) {
+    for reloaded in reloaded.iter() {
+        for (mut greacher, head_atlas, children) in &mut greachers {
+            let (id, palette) = &greacher.palette;
+            let Some(index) = greacher_palettes.find(id) else {
+                continue;
+            };
+
+            if reloaded.previous.get(index) != Some(palette) {
+                continue;
+            }
+
+            let edited = match greacher_palettes.palettes.get(index) {
+                Some(edited) if edited != palette => edited.clone(),
+                _ => continue,
+            };
+
+            // Greachers whose atlas or image was freed have nothing to recolor.
+            let Some(head_atlas) = texture_atlases.get(head_atlas) else {
+                continue;
+            };
+            let Some(head_texture) = images.get_mut(&head_atlas.texture) else {
+                continue;
+            };
+            let body = greacher.recolor(edited, head_texture);
+
+            // Sheets recolored on the CPU are updated by the IndexedImageServer,
+            // the ones recolored on the GPU only need to pick up the new
+            // palette texture.
+            for child in children {
+                match (&body, body_atlases.get(*child), &mut materials) {
+                    (Some(body), Ok(body_atlas), _) => {
+                        let Some(body_atlas) = texture_atlases.get(body_atlas) else {
+                            continue;
+                        };
+                        let Some(body_texture) = images.get_mut(&body_atlas.texture) else {
+                            continue;
+                        };
+                        *body_texture = body.clone();
+                    }
+                    (None, _, Some(materials)) => {
+                        if let Ok(material) = body_materials.get(*child) {
+                            PaletteSwapMaterial::refresh(materials, material);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Logs the code of the greacher closest to the mouse, for sharing it.
+fn share_greacher_near_mouse(
+    keys: Res<Input<KeyCode>>,
+    world_mouse: Res<WorldMouse>,
+    greachers: Query<(&Greacher, &Transform)>,
+) {
+    if !keys.just_pressed(KeyCode::C) {
+        return;
+    }
+
+    let nearest = greachers.iter().min_by(|(_, a), (_, b)| {
+        let a = a.translation.truncate().distance_squared(**world_mouse);
+        let b = b.translation.truncate().distance_squared(**world_mouse);
+
+        a.total_cmp(&b)
+    });
+
+    if let Some((greacher, _)) = nearest {
+        match greacher.to_code() {
+            Ok(code) => info!("{}: {}", greacher.name, code),
+            Err(why) => error!("Couldn't share {}: {}", greacher.name, why),
+        }
+    }
+}
+
 fn world_cursor_pos(
     wnds: Res<Windows>,
     mut world_mouse: ResMut<WorldMouse>,
@@ -170,7 +364,7 @@ fn world_cursor_pos(
     // check if the cursor is inside the window and get its position
     if let Some(screen_pos) = wnd.cursor_position() {
         // get the size of the window
-        let window_size = Vec2::new(wnd.width() as f32, wnd.height() as f32);
+        let window_size = Vec2::new(wnd.width(), wnd.height());
 
         // convert screen position [0..resolution] to ndc [-1..1] (gpu coordinates)
         let ndc = (screen_pos / window_size) * 2.0 - Vec2::ONE;
@@ -1,22 +1,128 @@
-use crate::{color::{Color, GreacherColorPalette}, util::SliceExt};
-use bevy::prelude::*;
+use crate::{
+    color::{Color, GreacherColorPalette},
+    index_keys::IndexKeys,
+};
+
+use super::{
+    components::{GreacherBodyType, GreacherStats},
+    genome::{gene_rng, Genome},
+    names::NameLanguage,
+    rarity::GreacherTraits,
+};
+use bevy::{
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
 use lazy_static::lazy_static;
 use rand::prelude::*;
+use std::ops::{Range, RangeInclusive};
 
-pub const GREACHER_HEAD_SIZE: usize = 8;
-pub const GREACHER_CANVAS_SIZE: usize = GREACHER_HEAD_SIZE + 2;
+/// Canvas size of a regular greacher head, including the 1 pixel border.
+pub const GREACHER_CANVAS_SIZE: usize = 10;
 
-lazy_static! {
-    static ref NAME_STARTS: Vec<&'static str> = vec![
-        "qu", "wr", "br", "tl", "p", "s", "d", "fw", "gh", "j", "kl", "l", "z", "cl", "v", "b",
-        "n", "m",
+/// Smallest canvas a head can be drawn on, a single pixel inside the border.
+/// Every shape and pattern is scaled down to fit anything from there up.
+pub const MIN_CANVAS_SIZE: usize = 3;
+
+/// Revisions of the generators. A new one is added whenever they change in a
+/// way that makes old seeds turn out differently, and the old code paths stay
+/// around so greachers keep looking the way they did when they were made.
+///
+/// Names, and the palettes greachers pick from and ramp their colors out of,
+/// count as generator output too, so changes to how languages or palettes
+/// are read need a new version as much as changes to the heads do.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GeneratorVersion {
+    /// Heads, names, palettes and stats rolled from the seed.
+    V1 = 1,
+    /// Rarity tiers and the traits that come with them.
+    V2 = 2,
+    /// Layered head patterns that keep to the silhouette.
+    V3 = 3,
+    /// Generated leg bodies instead of the hand drawn sheet.
+    V4 = 4,
+    /// Greachers bigger or smaller than usual.
+    V5 = 5,
+    /// Generated palettes to pick from, next to the loaded ones.
+    V6 = 6,
+}
+
+impl GeneratorVersion {
+    pub const ALL: [GeneratorVersion; 6] = [
+        GeneratorVersion::V1,
+        GeneratorVersion::V2,
+        GeneratorVersion::V3,
+        GeneratorVersion::V4,
+        GeneratorVersion::V5,
+        GeneratorVersion::V6,
     ];
-    static ref NAME_JOINS: Vec<&'static str> = vec!["a", "e", "i", "o", "u"];
-    static ref NAME_ENDS: Vec<&'static str> = vec!["g", "mb", "kl", "pw", "nk", "mk"];
-    static ref NAME_PREFIXES: Vec<&'static str> =
-        vec!["THE ", "big ", "little ", "tiny ", "baby ", "loser "];
-    static ref NAME_POSTFIXES: Vec<&'static str> =
-        vec!["ford", "ley", " the great", "pop", "wer", "ula", "io"];
+
+    /// The version new greachers are made with.
+    pub const LATEST: GeneratorVersion = GeneratorVersion::V6;
+
+    pub fn from_u8(version: u8) -> Option<GeneratorVersion> {
+        GeneratorVersion::ALL
+            .into_iter()
+            .find(|known| *known as u8 == version)
+    }
+
+    pub fn has_rarity(&self) -> bool {
+        *self >= GeneratorVersion::V2
+    }
+
+    pub fn has_layered_patterns(&self) -> bool {
+        *self >= GeneratorVersion::V3
+    }
+
+    pub fn has_generated_legs(&self) -> bool {
+        *self >= GeneratorVersion::V4
+    }
+
+    pub fn has_sizes(&self) -> bool {
+        *self >= GeneratorVersion::V5
+    }
+
+    pub fn has_generated_palettes(&self) -> bool {
+        *self >= GeneratorVersion::V6
+    }
+}
+
+/// Smallest and biggest a greacher can be, relative to the usual size.
+pub const GREACHER_SIZE_RANGE: RangeInclusive<f32> = 0.7..=1.4;
+
+/// Size of a single frame of a body atlas.
+pub const BODY_FRAME_SIZE: (usize, usize) = (8, 6);
+/// Body atlases hold idle frames on the first row, and run frames on the
+/// second.
+pub const BODY_ATLAS_COLUMNS: usize = 8;
+pub const BODY_ATLAS_ROWS: usize = 2;
+pub const BODY_IDLE_FRAMES: usize = 6;
+pub const BODY_RUN_FRAMES: usize = 8;
+
+/// Heads are laid out on a grid of this many cells per side, stretched over
+/// whatever canvas they're generated on.
+const HEAD_CELLS: isize = 8;
+
+/// Frames of a head atlas, in the order they're laid out from left to right.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeadFrame {
+    Idle,
+    Blink,
+    Squint,
+    OpenMouth,
+}
+
+impl HeadFrame {
+    pub const ALL: [HeadFrame; 4] = [
+        HeadFrame::Idle,
+        HeadFrame::Blink,
+        HeadFrame::Squint,
+        HeadFrame::OpenMouth,
+    ];
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -29,90 +135,689 @@ enum ColorMapping {
     White,
 }
 
-pub fn generate_greacher_name(rng: &mut SmallRng) -> String {
-    let mut name = match rng.gen_range(0..4) {
-        0 => format!(
-            "{}{}{}",
-            NAME_STARTS.random(rng),
-            NAME_JOINS.random(rng),
-            NAME_ENDS.random(rng)
-        ),
-        1 => format!(
-            "{}{}{}{}",
-            NAME_STARTS.random(rng),
-            NAME_JOINS.random(rng),
-            NAME_ENDS.random(rng),
-            NAME_JOINS.random(rng)
-        ),
-        2 => format!(
-            "{}{}{}{}",
-            NAME_STARTS.random(rng),
-            NAME_JOINS.random(rng),
-            NAME_STARTS.random(rng),
-            NAME_JOINS.random(rng)
-        ),
-        3 => format!(
-            "{}{}{}{}{}",
-            NAME_STARTS.random(rng),
-            NAME_JOINS.random(rng),
-            NAME_STARTS.random(rng),
-            NAME_JOINS.random(rng),
-            NAME_ENDS.random(rng)
-        ),
-        _ => panic!(),
+impl ColorMapping {
+    /// The slot of a classic four color palette this is drawn with.
+    fn slot(&self) -> Option<usize> {
+        match self {
+            ColorMapping::Dark => Some(0),
+            ColorMapping::Darkish => Some(1),
+            ColorMapping::Basic => Some(2),
+            ColorMapping::Highlight => Some(3),
+            ColorMapping::Transparent | ColorMapping::White => None,
+        }
+    }
+
+    fn fixed_color(&self) -> Color {
+        match self {
+            ColorMapping::White => Color::WHITE,
+            _ => Color::TRANSPARENT,
+        }
+    }
+
+    fn color(&self, palette: &GreacherColorPalette) -> Color {
+        match self.slot() {
+            Some(slot) => palette.ramp(slot, GreacherColorPalette::CLASSIC_SLOTS),
+            None => self.fixed_color(),
+        }
+    }
+
+    fn key(&self) -> Color {
+        match self.slot() {
+            Some(slot) => IndexKeys::CLASSIC[slot],
+            None => self.fixed_color(),
+        }
+    }
+}
+
+/// Names are built in two halves, the start from `start_rng` and the rest
+/// from `end_rng`, so offspring can mix their parents' names.
+pub fn generate_greacher_name(
+    language: &NameLanguage,
+    start_rng: &mut SmallRng,
+    end_rng: &mut SmallRng,
+) -> String {
+    language.generate(start_rng, end_rng)
+}
+
+pub fn generate_greacher_body_type(rng: &mut SmallRng) -> GreacherBodyType {
+    if rng.gen_range(0..4) == 0 {
+        GreacherBodyType::Wings
+    } else {
+        GreacherBodyType::Legs
+    }
+}
+
+/// Rolls how many times bigger than usual a greacher is. Most stay close to
+/// the usual size, with the extremes rare. Sizes are rounded so a regular
+/// head always comes out a whole number of pixels wide.
+pub fn generate_greacher_size(rng: &mut SmallRng) -> f32 {
+    let spread: f32 = (rng.gen_range(-1.0..1.0) + rng.gen_range(-1.0..1.0)) / 2.;
+
+    let size = if spread < 0. {
+        1. + spread * (1. - GREACHER_SIZE_RANGE.start())
+    } else {
+        1. + spread * (GREACHER_SIZE_RANGE.end() - 1.)
     };
+    let step = GREACHER_CANVAS_SIZE as f32;
+
+    (size * step).round() / step
+}
 
-    if rng.gen_range(0..10) == 0 {
-        name = format!("{}{}", NAME_PREFIXES.random(rng), name);
+pub fn generate_greacher_stats(rng: &mut SmallRng) -> GreacherStats {
+    GreacherStats {
+        speed: rng.gen_range(10.0..22.0),
+        acceleration: rng.gen_range(48.0..96.0),
+        max_health: rng.gen_range(6..=20) as f32,
+        strength: rng.gen_range(1..=10) as f32,
+        hunger_rate: rng.gen_range(0.5..2.0),
+        sight_radius: rng.gen_range(48.0..160.0),
     }
+}
 
-    if rng.gen_range(0..10) == 0 {
-        name = format!("{}{}", name, NAME_POSTFIXES.random(rng));
+/// Creates an empty head atlas, a row of [`HeadFrame`]s with sides of
+/// `canvas_size` pixels.
+pub fn create_head_image(canvas_size: usize) -> Image {
+    Image::new_fill(
+        Extent3d {
+            width: (canvas_size * HeadFrame::ALL.len()) as u32,
+            height: canvas_size as u32,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 0],
+        TextureFormat::Rgba8Unorm,
+    )
+}
+
+/// Draws every frame of a head onto `image`, at whatever size its frames are.
+pub fn generate_greacher_head_texture(
+    genome: &Genome,
+    image: &mut Image,
+    palette: &GreacherColorPalette,
+    traits: GreacherTraits,
+    version: GeneratorVersion,
+) {
+    draw_head_atlas(
+        genome,
+        image,
+        palette,
+        traits,
+        HeadPatterns::Generated(version),
+    );
+}
+
+/// Draws a head like [`generate_greacher_head_texture`], but with only the
+/// given pattern on it.
+pub fn generate_head_pattern_preview(
+    pattern: &HeadPattern,
+    genome: &Genome,
+    image: &mut Image,
+    palette: &GreacherColorPalette,
+    traits: GreacherTraits,
+) {
+    draw_head_atlas(genome, image, palette, traits, HeadPatterns::Only(pattern));
+}
+
+/// Which patterns go on a head drawn by [`draw_head_atlas`].
+enum HeadPatterns<'a> {
+    /// Whatever the pattern gene rolls, the way the given version rolled it.
+    Generated(GeneratorVersion),
+    Only(&'a HeadPattern),
+}
+
+fn draw_head_atlas(
+    genome: &Genome,
+    image: &mut Image,
+    palette: &GreacherColorPalette,
+    traits: GreacherTraits,
+    patterns: HeadPatterns,
+) {
+    let size = image.texture_descriptor.size;
+    assert_eq!(
+        size.width,
+        size.height * HeadFrame::ALL.len() as u32,
+        "Head atlases have to be a row of square frames!"
+    );
+
+    let mut canvas = HeadCanvas::new(size.height as usize);
+
+    let bounds = generate_head_shape(
+        &mut canvas,
+        &mut gene_rng(genome.head_size),
+        &mut gene_rng(genome.head_shape),
+    );
+    let mut pattern_rng = gene_rng(genome.head_pattern);
+    match patterns {
+        HeadPatterns::Generated(version) if version.has_layered_patterns() => {
+            generate_head_pattern(&mut canvas, bounds, &mut pattern_rng)
+        }
+        HeadPatterns::Generated(_) => draw_legacy_walk_pattern(&mut canvas, &mut pattern_rng),
+        HeadPatterns::Only(pattern) => (pattern.draw)(&mut canvas, bounds, &mut pattern_rng),
     }
+    generate_eyes(
+        &mut canvas,
+        &mut gene_rng(genome.eye_size),
+        &mut gene_rng(genome.eye_position),
+        traits.contains(GreacherTraits::ExtraEyes),
+    );
 
-    name.to_uppercase()
+    let frames: Vec<HeadCanvas> = HeadFrame::ALL
+        .iter()
+        .map(|frame| canvas.with_expression(*frame, bounds))
+        .collect();
+
+    image.data = create_atlas_data(&frames, palette);
+}
+
+/// Square grid of [`ColorMapping`]s a head is drawn on.
+///
+/// Writes outside of the canvas are ignored, so primitives don't have to
+/// clip themselves.
+#[derive(Clone)]
+struct HeadCanvas {
+    size: usize,
+    data: Vec<ColorMapping>,
 }
 
-pub fn generate_greacher_head_texture(rng: &mut SmallRng, image: &mut Image, palette: &GreacherColorPalette) {
-    let mut template = vec![ColorMapping::Transparent; GREACHER_CANVAS_SIZE * GREACHER_CANVAS_SIZE];
+impl HeadCanvas {
+    fn new(size: usize) -> Self {
+        assert!(
+            size >= MIN_CANVAS_SIZE,
+            "Head canvases have to be at least {} pixels, not {}",
+            MIN_CANVAS_SIZE,
+            size
+        );
+
+        HeadCanvas {
+            size,
+            data: vec![ColorMapping::Transparent; size * size],
+        }
+    }
+
+    fn get(&self, x: isize, y: isize) -> ColorMapping {
+        if x < 0 || y < 0 || x as usize >= self.size || y as usize >= self.size {
+            return ColorMapping::Transparent;
+        }
+
+        self.data[y as usize * self.size + x as usize]
+    }
+
+    fn set(&mut self, x: isize, y: isize, color: ColorMapping) {
+        if x < 0 || y < 0 || x as usize >= self.size || y as usize >= self.size {
+            return;
+        }
+
+        self.data[y as usize * self.size + x as usize] = color;
+    }
 
-    generate_head_shape(&mut template, rng);
-    generate_head_pattern(&mut template, rng);
-    generate_eyes(&mut template, rng);
+    /// Sets the pixel only if it's part of the head already, so patterns keep
+    /// to its silhouette.
+    fn paint(&mut self, x: isize, y: isize, color: ColorMapping) {
+        if self.get(x, y) != ColorMapping::Transparent {
+            self.set(x, y, color);
+        }
+    }
 
-    image.data = create_color_data(&template, palette);
+    fn paint_mirrored(&mut self, x: isize, y: isize, color: ColorMapping) {
+        self.paint(x, y, color);
+        self.paint(self.mirror_x(x), y, color);
+    }
+
+    /// Sets the pixel and its reflection across the vertical center line.
+    fn set_mirrored(&mut self, x: isize, y: isize, color: ColorMapping) {
+        self.set(x, y, color);
+        self.set(self.mirror_x(x), y, color);
+    }
+
+    fn mirror_x(&self, x: isize) -> isize {
+        self.size as isize - 1 - x
+    }
+
+    /// Size of the area inside the border.
+    fn head_size(&self) -> isize {
+        self.size as isize - 2
+    }
+
+    /// Scales a length meant for a regular, 8 pixel head to this canvas.
+    fn scale(&self, length: isize) -> isize {
+        (length * self.head_size() / HEAD_CELLS).max(1)
+    }
+
+    /// First pixel of a layout cell along either axis.
+    fn cell_start(&self, cell: isize) -> isize {
+        1 + cell * self.head_size() / HEAD_CELLS
+    }
+
+    /// A copy of the head making the given face. Everything but the idle face
+    /// is drawn over the eyes and mouth of this one.
+    fn with_expression(&self, frame: HeadFrame, bounds: HeadBounds) -> HeadCanvas {
+        let mut face = self.clone();
+
+        for j in 0..self.size as isize {
+            for i in 0..self.size as isize {
+                if self.get(i, j) != ColorMapping::White {
+                    continue;
+                }
+
+                let top_of_eye = self.get(i, j - 1) != ColorMapping::White;
+                let bottom_of_eye = self.get(i, j + 1) != ColorMapping::White;
+
+                match frame {
+                    HeadFrame::Blink => face.set(
+                        i,
+                        j,
+                        if bottom_of_eye {
+                            ColorMapping::Dark
+                        } else {
+                            ColorMapping::Darkish
+                        },
+                    ),
+                    // Tall eyes get their top row shut. Short ones would close
+                    // completely, so they get narrower instead, or dimmed if
+                    // they're only a pixel wide.
+                    HeadFrame::Squint if top_of_eye && !bottom_of_eye => {
+                        face.set(i, j, ColorMapping::Dark)
+                    }
+                    HeadFrame::Squint if top_of_eye => {
+                        let inwards = if i < self.size as isize / 2 { 1 } else { -1 };
+                        let outwards = self.get(i - inwards, j);
+
+                        if self.get(i + inwards, j) == ColorMapping::White {
+                            face.set(i, j, ColorMapping::Dark)
+                        } else if outwards != ColorMapping::White {
+                            face.set(i, j, ColorMapping::Highlight)
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if frame == HeadFrame::OpenMouth {
+            face.draw_mouth(bounds);
+        }
+
+        face
+    }
+
+    /// Opens a mouth at the bottom middle of the head, above its bottom edge.
+    fn draw_mouth(&mut self, bounds: HeadBounds) {
+        let mut width = (bounds.width() / 3).max(2);
+        if (self.size as isize - width) % 2 != 0 {
+            width -= 1;
+        }
+        let height = self.scale(2).min(bounds.height() - 2).max(1);
+
+        let min_x = (self.size as isize - width) / 2;
+
+        for j in (bounds.max_y - height)..bounds.max_y {
+            for i in min_x..(min_x + width) {
+                let current = self.get(i, j);
+
+                if current != ColorMapping::Transparent && current != ColorMapping::White {
+                    self.set(i, j, ColorMapping::Dark);
+                }
+            }
+        }
+    }
+
+    /// Fills a block of layout cells, and its reflection. Blocks are always at
+    /// least a pixel wide, even when cells are smaller than that.
+    fn fill_cells_mirrored(&mut self, x: Range<isize>, y: Range<isize>, color: ColorMapping) {
+        let (x, y) = self.cell_pixels(x, y);
+
+        for j in y {
+            for i in x.clone() {
+                self.set_mirrored(i, j, color);
+            }
+        }
+    }
+
+    /// Like [`HeadCanvas::fill_cells_mirrored`], but only over the head.
+    fn paint_cells_mirrored(&mut self, x: Range<isize>, y: Range<isize>, color: ColorMapping) {
+        let (x, y) = self.cell_pixels(x, y);
+
+        for j in y {
+            for i in x.clone() {
+                self.paint_mirrored(i, j, color);
+            }
+        }
+    }
+
+    /// Pixels covered by a block of layout cells, at least one along each axis.
+    fn cell_pixels(&self, x: Range<isize>, y: Range<isize>) -> (Range<isize>, Range<isize>) {
+        let (min_x, min_y) = (self.cell_start(x.start), self.cell_start(y.start));
+        let max_x = self.cell_start(x.end).max(min_x + 1);
+        let max_y = self.cell_start(y.end).max(min_y + 1);
+
+        (min_x..max_x, min_y..max_y)
+    }
+}
+
+/// Box covered by the base silhouette of a head, inclusive on all sides.
+#[derive(Clone, Copy, Debug)]
+struct HeadBounds {
+    min_x: isize,
+    max_x: isize,
+    min_y: isize,
+    max_y: isize,
+}
+
+impl HeadBounds {
+    fn width(&self) -> isize {
+        self.max_x - self.min_x + 1
+    }
+
+    fn height(&self) -> isize {
+        self.max_y - self.min_y + 1
+    }
+}
+
+/// A building block of a head silhouette.
+///
+/// Primitives draw against the bounds of the base shape. Unless they are
+/// meant to be lopsided, they draw through [`HeadCanvas::set_mirrored`], and
+/// lopsided ones stay above the head so the pattern and eye passes still see
+/// a symmetric face.
+struct ShapePrimitive {
+    /// Relative chance of the primitive being picked.
+    weight: u32,
+    draw: fn(&mut HeadCanvas, HeadBounds, &mut SmallRng),
+}
+
+lazy_static! {
+    /// Silhouettes a head starts from, one per head.
+    static ref HEAD_BASES: Vec<ShapePrimitive> = vec![
+        ShapePrimitive { weight: 4, draw: draw_rectangle_base },
+        ShapePrimitive { weight: 3, draw: draw_blob_base },
+        ShapePrimitive { weight: 2, draw: draw_triangle_base },
+    ];
+    /// Features stuck onto the base, any number of them per head.
+    static ref HEAD_FEATURES: Vec<ShapePrimitive> = vec![
+        ShapePrimitive { weight: 3, draw: draw_horns },
+        ShapePrimitive { weight: 3, draw: draw_ears },
+        ShapePrimitive { weight: 2, draw: draw_antennae },
+        ShapePrimitive { weight: 3, draw: draw_snout },
+        ShapePrimitive { weight: 1, draw: draw_lopsided_horn },
+        ShapePrimitive { weight: 1, draw: draw_tuft },
+    ];
 }
 
-fn generate_head_shape(data: &mut [ColorMapping], rng: &mut SmallRng) -> (usize, usize) {
-    let size = (rng.gen_range(3..5) * 2, rng.gen_range(4..9));
+const MAX_HEAD_FEATURES: usize = 2;
+
+fn generate_head_shape(
+    canvas: &mut HeadCanvas,
+    size_rng: &mut SmallRng,
+    shape_rng: &mut SmallRng,
+) -> HeadBounds {
+    let head_size = canvas.head_size();
+    let mut width = head_size * size_rng.gen_range(3..5) / 4;
+    let height = (head_size * size_rng.gen_range(4..9) / HEAD_CELLS).max(2);
+
+    // The head has to be centered on the canvas to stay symmetric.
+    if (head_size - width) % 2 != 0 {
+        width -= 1;
+    }
+
+    // Tiny heads can round down to nothing.
+    width = width.max(2 - head_size % 2);
+
+    let min_x = (canvas.size as isize - width) / 2;
+
+    let bounds = HeadBounds {
+        min_x,
+        max_x: min_x + width - 1,
+        min_y: head_size - height + 1,
+        max_y: head_size,
+    };
 
-    let min_y = 1 + 8 - size.1;
-    let max_y = GREACHER_HEAD_SIZE;
+    let base = HEAD_BASES
+        .choose_weighted(shape_rng, |primitive| primitive.weight)
+        .unwrap();
+    (base.draw)(canvas, bounds, shape_rng);
 
-    let min_x = 5 - size.0 / 2;
-    let max_x = 4 + size.0 / 2;
+    for _ in 0..shape_rng.gen_range(0..=MAX_HEAD_FEATURES) {
+        let feature = HEAD_FEATURES
+            .choose_weighted(shape_rng, |primitive| primitive.weight)
+            .unwrap();
+        (feature.draw)(canvas, bounds, shape_rng);
+    }
 
-    for j in min_y..=max_y {
-        for i in min_x..=max_x {
-            data[j * GREACHER_CANVAS_SIZE + i] = ColorMapping::Basic;
+    bounds
+}
 
-            if (j == min_y || j == max_y) && (i == min_x || i == max_x) {
-                data[j * GREACHER_CANVAS_SIZE + i] = ColorMapping::Darkish;
+fn draw_rectangle_base(canvas: &mut HeadCanvas, bounds: HeadBounds, _rng: &mut SmallRng) {
+    for j in bounds.min_y..=bounds.max_y {
+        for i in bounds.min_x..=bounds.max_x {
+            if (j == bounds.min_y || j == bounds.max_y) && (i == bounds.min_x || i == bounds.max_x)
+            {
+                canvas.set(i, j, ColorMapping::Darkish);
+            } else {
+                canvas.set(i, j, ColorMapping::Basic);
             }
         }
     }
+}
+
+fn draw_blob_base(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    // Bigger blobs get their corners cut deeper.
+    let roundness = if bounds.width() >= 8 && bounds.height() >= 6 {
+        canvas.scale(rng.gen_range(1..=2))
+    } else {
+        canvas.scale(1)
+    };
 
-    size
+    for j in bounds.min_y..=bounds.max_y {
+        let from_edge = (j - bounds.min_y).min(bounds.max_y - j);
+        let inset = (roundness - from_edge).max(0);
+
+        for i in (bounds.min_x + inset)..=(canvas.mirror_x(bounds.min_x + inset)) {
+            canvas.set(i, j, ColorMapping::Basic);
+        }
+
+        if inset > 0 {
+            canvas.set_mirrored(bounds.min_x + inset, j, ColorMapping::Darkish);
+        }
+    }
 }
 
-fn generate_head_pattern(data: &mut [ColorMapping], rng: &mut SmallRng) {
-    let mut generator: (usize, usize) = (1 + rng.gen_range(0..8), 1 + rng.gen_range(0..8));
+fn draw_triangle_base(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    let top_inset = (bounds.width() / 2 - 1).max(0);
+    let steepness = rng.gen_range(1..=2);
+
+    for j in bounds.min_y..=bounds.max_y {
+        let inset = (top_inset - (j - bounds.min_y) / steepness).max(0);
+
+        for i in (bounds.min_x + inset)..=(canvas.mirror_x(bounds.min_x + inset)) {
+            canvas.set(i, j, ColorMapping::Basic);
+        }
+
+        canvas.set_mirrored(bounds.min_x + inset, j, ColorMapping::Darkish);
+    }
+}
+
+fn draw_horns(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    let length = canvas.scale(rng.gen_range(1..=3));
+    let curl = rng.gen_bool(0.5);
+
+    for k in 1..=length {
+        let x = if curl {
+            bounds.min_x - (k - 1) / 2
+        } else {
+            bounds.min_x
+        };
+        canvas.set_mirrored(x, bounds.min_y - k, ColorMapping::Darkish);
+    }
+}
+
+fn draw_ears(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    let height = canvas.scale(rng.gen_range(1..=2));
+
+    for k in 1..=height {
+        for i in bounds.min_x..(bounds.min_x + 1 + height - k) {
+            canvas.set_mirrored(i, bounds.min_y - k, ColorMapping::Basic);
+        }
+    }
+
+    canvas.set_mirrored(bounds.min_x, bounds.min_y - height, ColorMapping::Darkish);
+}
+
+fn draw_antennae(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    let x = bounds.min_x + rng.gen_range(1..(bounds.width() / 2).max(2));
+    let length = canvas.scale(rng.gen_range(2..=3));
+
+    for k in 1..length {
+        canvas.set_mirrored(x, bounds.min_y - k, ColorMapping::Darkish);
+    }
+
+    canvas.set_mirrored(x, bounds.min_y - length, ColorMapping::Highlight);
+}
+
+fn draw_snout(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    let half_width = canvas.scale(rng.gen_range(1..=2));
+    let center = canvas.size as isize / 2;
+
+    for i in (center - half_width)..=canvas.mirror_x(center - half_width) {
+        canvas.set(i, bounds.max_y + 1, ColorMapping::Basic);
+    }
+
+    canvas.set_mirrored(center - half_width, bounds.max_y + 1, ColorMapping::Darkish);
+    canvas.set_mirrored(center - 1, bounds.max_y, ColorMapping::Dark);
+}
+
+fn draw_lopsided_horn(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    let length = canvas.scale(rng.gen_range(2..=3));
+    let x = if rng.gen_bool(0.5) {
+        bounds.min_x + 1
+    } else {
+        bounds.max_x - 1
+    };
+
+    for k in 1..=length {
+        canvas.set(x, bounds.min_y - k, ColorMapping::Darkish);
+    }
+}
+
+fn draw_tuft(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    // The tuft grows between the edges of the head.
+    if bounds.width() <= 2 {
+        return;
+    }
+
+    let x = rng.gen_range(bounds.min_x + 1..bounds.max_x);
+
+    canvas.set(x, bounds.min_y - 1, ColorMapping::Highlight);
+    canvas.set(
+        x + rng.gen_range(-1..=1),
+        bounds.min_y - 2,
+        ColorMapping::Highlight,
+    );
+}
+
+/// A way of coloring the surface of a head.
+///
+/// Patterns only ever paint over the head, see [`HeadCanvas::paint`], and
+/// stay symmetric.
+pub struct HeadPattern {
+    pub name: &'static str,
+    /// Relative chance of the pattern being picked.
+    weight: u32,
+    draw: fn(&mut HeadCanvas, HeadBounds, &mut SmallRng),
+}
+
+lazy_static! {
+    pub static ref HEAD_PATTERNS: Vec<HeadPattern> = vec![
+        HeadPattern {
+            name: "walk",
+            weight: 4,
+            draw: draw_walk_pattern
+        },
+        HeadPattern {
+            name: "stripes",
+            weight: 3,
+            draw: draw_stripes_pattern
+        },
+        HeadPattern {
+            name: "spots",
+            weight: 3,
+            draw: draw_spots_pattern
+        },
+        HeadPattern {
+            name: "checker",
+            weight: 1,
+            draw: draw_checker_pattern
+        },
+        HeadPattern {
+            name: "bands",
+            weight: 2,
+            draw: draw_bands_pattern
+        },
+        HeadPattern {
+            name: "belly",
+            weight: 3,
+            draw: draw_belly_pattern
+        },
+        HeadPattern {
+            name: "freckles",
+            weight: 2,
+            draw: draw_freckles_pattern
+        },
+    ];
+}
+
+pub fn find_head_pattern(name: &str) -> Option<&'static HeadPattern> {
+    HEAD_PATTERNS.iter().find(|pattern| pattern.name == name)
+}
+
+/// Layers one or two different patterns over the head, in the order they
+/// were picked.
+fn generate_head_pattern(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    let first = HEAD_PATTERNS
+        .choose_weighted(rng, |pattern| pattern.weight)
+        .unwrap();
+    (first.draw)(canvas, bounds, rng);
+
+    if rng.gen_bool(0.5) {
+        let second = HEAD_PATTERNS
+            .choose_weighted(rng, |pattern| {
+                if pattern.name == first.name {
+                    0
+                } else {
+                    pattern.weight
+                }
+            })
+            .unwrap();
+        (second.draw)(canvas, bounds, rng);
+    }
+}
+
+fn draw_walk_pattern(canvas: &mut HeadCanvas, _bounds: HeadBounds, rng: &mut SmallRng) {
+    walk_cells(canvas, rng, HeadCanvas::paint_cells_mirrored);
+}
+
+/// The only pattern before [`GeneratorVersion::V3`], which could spill out of
+/// the silhouette.
+fn draw_legacy_walk_pattern(canvas: &mut HeadCanvas, rng: &mut SmallRng) {
+    walk_cells(canvas, rng, HeadCanvas::fill_cells_mirrored);
+}
+
+/// Colors the cells along a random walk over the layout grid.
+fn walk_cells(
+    canvas: &mut HeadCanvas,
+    rng: &mut SmallRng,
+    fill: fn(&mut HeadCanvas, Range<isize>, Range<isize>, ColorMapping),
+) {
+    let mut generator: (isize, isize) = (1 + rng.gen_range(0..8), 1 + rng.gen_range(0..8));
 
     let pattern_length: usize = rng.gen_range(8..16);
 
     for _ in 0..pattern_length {
-        data[generator.1 * GREACHER_CANVAS_SIZE + generator.0] = ColorMapping::Highlight;
-        data[generator.1 * GREACHER_CANVAS_SIZE + (9 - generator.0)] = ColorMapping::Highlight;
+        fill(
+            canvas,
+            (generator.0 - 1)..generator.0,
+            (generator.1 - 1)..generator.1,
+            ColorMapping::Highlight,
+        );
 
         let dir: (isize, isize) = match rng.gen_range(0usize..4usize) {
             0 => (1, 0),
@@ -123,85 +828,409 @@ fn generate_head_pattern(data: &mut [ColorMapping], rng: &mut SmallRng) {
         };
 
         generator = (
-            (1 + (generator.0 as isize + dir.0 + GREACHER_HEAD_SIZE as isize)
-                % GREACHER_HEAD_SIZE as isize) as usize,
-            (1 + (generator.1 as isize + dir.1 + GREACHER_HEAD_SIZE as isize)
-                % GREACHER_HEAD_SIZE as isize) as usize,
+            1 + (generator.0 + dir.0 + HEAD_CELLS) % HEAD_CELLS,
+            1 + (generator.1 + dir.1 + HEAD_CELLS) % HEAD_CELLS,
+        );
+    }
+}
+
+/// Evenly spaced stripes, running across or down the head.
+fn draw_stripes_pattern(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    let thickness = canvas.scale(1);
+    let spacing = thickness * rng.gen_range(2..4);
+    let offset = rng.gen_range(0..spacing);
+    let color = *[ColorMapping::Darkish, ColorMapping::Highlight]
+        .choose(rng)
+        .unwrap();
+
+    if rng.gen_bool(0.5) {
+        for j in bounds.min_y..=bounds.max_y {
+            if (j - bounds.min_y + offset) % spacing < thickness {
+                for i in bounds.min_x..=bounds.max_x {
+                    canvas.paint(i, j, color);
+                }
+            }
+        }
+    } else {
+        // Measured from the middle out, so both sides get the same stripes.
+        let center = canvas.size as isize / 2;
+
+        for i in center..canvas.size as isize {
+            if (i - center + offset) % spacing < thickness {
+                for j in 0..canvas.size as isize {
+                    canvas.paint_mirrored(i, j, color);
+                }
+            }
+        }
+    }
+}
+
+/// A few round spots scattered over the head.
+fn draw_spots_pattern(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    let radius = canvas.scale(rng.gen_range(1..3)) as f32 / 2.;
+
+    for _ in 0..rng.gen_range(1..4) {
+        let center = (
+            rng.gen_range(bounds.min_x..=bounds.max_x) as f32 + 0.5,
+            rng.gen_range(bounds.min_y..=bounds.max_y) as f32 + 0.5,
         );
+
+        for j in bounds.min_y..=bounds.max_y {
+            for i in bounds.min_x..=bounds.max_x {
+                let distance = (i as f32 + 0.5 - center.0).hypot(j as f32 + 0.5 - center.1);
+
+                if distance <= radius {
+                    canvas.paint_mirrored(i, j, ColorMapping::Darkish);
+                }
+            }
+        }
+    }
+}
+
+/// Alternating squares over the whole head.
+fn draw_checker_pattern(canvas: &mut HeadCanvas, _bounds: HeadBounds, rng: &mut SmallRng) {
+    let square = canvas.scale(rng.gen_range(1..3));
+    let center = canvas.size as isize / 2;
+
+    for j in 0..canvas.size as isize {
+        for i in center..canvas.size as isize {
+            if ((i - center) / square + j / square) % 2 == 0 {
+                canvas.paint_mirrored(i, j, ColorMapping::Darkish);
+            }
+        }
+    }
+}
+
+/// A light band over the top of the head and a dark one under it, like
+/// shading.
+fn draw_bands_pattern(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    let band = (bounds.height() * rng.gen_range(1..3) / 6).max(1);
+
+    for i in 0..canvas.size as isize {
+        for j in bounds.min_y..(bounds.min_y + band) {
+            canvas.paint(i, j, ColorMapping::Highlight);
+        }
+
+        for j in (bounds.max_y - band + 1)..=bounds.max_y {
+            canvas.paint(i, j, ColorMapping::Darkish);
+        }
     }
 }
 
-fn generate_eyes(data: &mut [ColorMapping], rng: &mut SmallRng) {
-    let eye_size = (rng.gen_range(1..3), rng.gen_range(1..3));
-    let eye_pos = (1 + rng.gen_range(0..2), 1 + rng.gen_range(0..7));
+/// A light, rounded patch on the lower middle of the head.
+fn draw_belly_pattern(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    let half_width = bounds.width() as f32 * rng.gen_range(0.2..0.35);
+    let height = bounds.height() as f32 * rng.gen_range(0.4..0.6);
+    let center = (canvas.size as f32 / 2., bounds.max_y as f32 + 1.);
+
+    for j in bounds.min_y..=bounds.max_y {
+        for i in bounds.min_x..=bounds.max_x {
+            let x = (i as f32 + 0.5 - center.0) / half_width;
+            let y = (j as f32 + 0.5 - center.1) / height;
 
-    for j in eye_pos.1..(eye_pos.1 + eye_size.1) {
-        for i in eye_pos.0..(eye_pos.0 + eye_size.0) {
-            data[j * GREACHER_CANVAS_SIZE + i] = ColorMapping::White;
-            data[j * GREACHER_CANVAS_SIZE + (GREACHER_CANVAS_SIZE - 1 - i)] = ColorMapping::White;
+            if x * x + y * y <= 1. {
+                canvas.paint(i, j, ColorMapping::Highlight);
+            }
         }
     }
+}
+
+/// Single pixel dots, sparser than spots.
+fn draw_freckles_pattern(canvas: &mut HeadCanvas, bounds: HeadBounds, rng: &mut SmallRng) {
+    let count = (bounds.width() * bounds.height() / 12).max(1);
 
-    for j in 0..GREACHER_CANVAS_SIZE {
-        for i in 0..GREACHER_CANVAS_SIZE {
-            let current = data[j * GREACHER_CANVAS_SIZE + i];
+    for _ in 0..count {
+        canvas.paint_mirrored(
+            rng.gen_range(bounds.min_x..=bounds.max_x),
+            rng.gen_range(bounds.min_y..=bounds.max_y),
+            ColorMapping::Darkish,
+        );
+    }
+}
+
+fn generate_eyes(
+    canvas: &mut HeadCanvas,
+    size_rng: &mut SmallRng,
+    position_rng: &mut SmallRng,
+    extra_pair: bool,
+) {
+    let eye_size = (size_rng.gen_range(1..3), size_rng.gen_range(1..3));
+    let eye_pos = (
+        1 + position_rng.gen_range(0..2),
+        1 + position_rng.gen_range(0..7),
+    );
+
+    canvas.fill_cells_mirrored(
+        (eye_pos.0 - 1)..(eye_pos.0 - 1 + eye_size.0),
+        (eye_pos.1 - 1)..(eye_pos.1 - 1 + eye_size.1),
+        ColorMapping::White,
+    );
+
+    if extra_pair {
+        // A single cell pair closer to the middle, on rows clear of the main
+        // eyes so the two don't merge.
+        let rows: Vec<isize> = (0..7)
+            .filter(|row| *row < eye_pos.1 - 2 || *row > eye_pos.1 - 1 + eye_size.1)
+            .collect();
+        let x = (eye_pos.0 + eye_size.0).min(2);
+        let y = *rows.choose(position_rng).unwrap();
+
+        canvas.fill_cells_mirrored(x..(x + 1), y..(y + 1), ColorMapping::White);
+    }
+
+    for j in 0..canvas.size as isize {
+        for i in 0..canvas.size as isize {
+            let current = canvas.get(i, j);
 
             if current != ColorMapping::White && current != ColorMapping::Transparent {
                 let nearby = [
-                    if j > 0 {
-                        data[(j - 1) * 10 + i]
-                    } else {
-                        ColorMapping::Transparent
-                    },
-                    if j < GREACHER_CANVAS_SIZE - 1 {
-                        data[(j + 1) * 10 + i]
-                    } else {
-                        ColorMapping::Transparent
-                    },
-                    if i > 0 {
-                        data[j * 10 + (i - 1)]
-                    } else {
-                        ColorMapping::Transparent
-                    },
-                    if i < GREACHER_CANVAS_SIZE - 1 {
-                        data[j * 10 + (i + 1)]
-                    } else {
-                        ColorMapping::Transparent
-                    },
+                    canvas.get(i, j - 1),
+                    canvas.get(i, j + 1),
+                    canvas.get(i - 1, j),
+                    canvas.get(i + 1, j),
                 ];
 
-                for e in nearby {
-                    if e == ColorMapping::White {
-                        data[j * GREACHER_CANVAS_SIZE + i] = ColorMapping::Dark;
-                        break;
-                    }
+                if nearby.contains(&ColorMapping::White) {
+                    canvas.set(i, j, ColorMapping::Dark);
                 }
             }
         }
     }
 }
 
-fn create_color_data(template: &[ColorMapping], palette: &GreacherColorPalette) -> Vec<u8> {
-    let mut data = vec![0u8; GREACHER_CANVAS_SIZE * GREACHER_CANVAS_SIZE * 4];
+/// Proportions of a generated, legged body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BodyShape {
+    /// Legs come in mirrored pairs.
+    pub leg_pairs: isize,
+    pub leg_length: isize,
+    /// Half the width of the torso, which the outermost legs sit at the edges
+    /// of.
+    pub stance: isize,
+    /// Length of the tail, if there is one.
+    pub tail: Option<isize>,
+}
+
+pub fn generate_greacher_body_shape(rng: &mut SmallRng) -> BodyShape {
+    let stance = rng.gen_range(2..=4);
 
-    for j in 0..GREACHER_CANVAS_SIZE {
-        for i in 0..GREACHER_CANVAS_SIZE {
-            let col = match template[j * GREACHER_CANVAS_SIZE + i] {
-                ColorMapping::Transparent => Color::new(0, 0, 0, 0),
-                ColorMapping::Dark => palette.dark,
-                ColorMapping::Darkish => palette.darkish,
-                ColorMapping::Basic => palette.basic,
-                ColorMapping::Highlight => palette.highlight,
-                ColorMapping::White => Color::new(255, 255, 255, 255),
+    BodyShape {
+        leg_pairs: rng.gen_range(1..=3).min(stance),
+        leg_length: rng.gen_range(1..=3),
+        stance,
+        tail: rng.gen_bool(0.3).then(|| rng.gen_range(1..=2)),
+    }
+}
+
+/// How far the torso sinks on each idle frame.
+const IDLE_BOB: [isize; BODY_IDLE_FRAMES] = [0, 0, 0, 1, 1, 0];
+/// How far the torso sinks on each run frame.
+const RUN_BOB: [isize; BODY_RUN_FRAMES] = [0, 1, 0, 0, 0, 1, 0, 0];
+/// Horizontal offset of a foot from its hip, over a stride.
+const RUN_STRIDE: [isize; BODY_RUN_FRAMES] = [0, 1, 1, 0, 0, -1, -1, 0];
+/// Whether a foot is off the ground, over a stride.
+const RUN_LIFT: [bool; BODY_RUN_FRAMES] = [false, false, true, true, false, false, false, false];
+
+/// Draws an idle and run cycle for a body, in the layout of
+/// [`BODY_ATLAS_COLUMNS`] by [`BODY_ATLAS_ROWS`] frames.
+///
+/// The atlas uses the [`IndexKeys::CLASSIC`] key colors, so it gets
+/// recolored like the hand drawn sheets.
+pub fn generate_greacher_body_texture(shape: &BodyShape) -> Image {
+    let (frame_width, frame_height) = BODY_FRAME_SIZE;
+    let mut atlas = BodyAtlas {
+        width: frame_width * BODY_ATLAS_COLUMNS,
+        data: vec![
+            ColorMapping::Transparent;
+            frame_width * BODY_ATLAS_COLUMNS * frame_height * BODY_ATLAS_ROWS
+        ],
+    };
+
+    for (frame, bob) in IDLE_BOB.iter().enumerate() {
+        draw_body_frame(&mut atlas, shape, (frame, 0), *bob, None);
+    }
+
+    for (frame, bob) in RUN_BOB.iter().enumerate() {
+        draw_body_frame(&mut atlas, shape, (frame, 1), *bob, Some(frame));
+    }
+
+    let data = Color::to_pixels(atlas.data.iter().map(ColorMapping::key));
+
+    Image::new(
+        Extent3d {
+            width: atlas.width as u32,
+            height: (frame_height * BODY_ATLAS_ROWS) as u32,
+            ..Default::default()
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+struct BodyAtlas {
+    width: usize,
+    data: Vec<ColorMapping>,
+}
+
+impl BodyAtlas {
+    /// Sets a pixel of a frame, ignoring writes that fall outside of it.
+    fn set(&mut self, frame: (usize, usize), x: isize, y: isize, color: ColorMapping) {
+        let (frame_width, frame_height) = BODY_FRAME_SIZE;
+
+        if x < 0 || y < 0 || x as usize >= frame_width || y as usize >= frame_height {
+            return;
+        }
+
+        let x = frame.0 * frame_width + x as usize;
+        let y = frame.1 * frame_height + y as usize;
+
+        self.data[y * self.width + x] = color;
+    }
+}
+
+/// Draws a single frame, with the legs in stride if `run_frame` is given.
+fn draw_body_frame(
+    atlas: &mut BodyAtlas,
+    shape: &BodyShape,
+    frame: (usize, usize),
+    bob: isize,
+    run_frame: Option<usize>,
+) {
+    let (frame_width, frame_height) = (BODY_FRAME_SIZE.0 as isize, BODY_FRAME_SIZE.1 as isize);
+    let ground = frame_height - 1;
+    // Short legs can't bend, so the torso stays put on them.
+    let bob = if shape.leg_length > 1 { bob } else { 0 };
+
+    let top = bob;
+    let hip = ground - shape.leg_length + bob;
+    let min_x = frame_width / 2 - shape.stance;
+    let max_x = frame_width / 2 + shape.stance - 1;
+
+    for y in top..=hip {
+        for x in min_x..=max_x {
+            let rounded_corner = shape.stance > 2 && y == top && (x == min_x || x == max_x);
+
+            if rounded_corner {
+                continue;
+            }
+
+            let color = if y == hip {
+                ColorMapping::Darkish
+            } else {
+                ColorMapping::Basic
+            };
+            atlas.set(frame, x, y, color);
+        }
+    }
+
+    for pair in 0..shape.leg_pairs {
+        let spread = if shape.leg_pairs > 1 {
+            pair * (shape.stance - 1) / (shape.leg_pairs - 1)
+        } else {
+            0
+        };
+        let left = min_x + spread;
+
+        for (side, hip_x) in [(0, left), (1, frame_width - 1 - left)] {
+            let (stride, lifted) = match run_frame {
+                Some(run_frame) => {
+                    // Legs on either side, and neighboring pairs, step in turns.
+                    let step = (run_frame + side * 4 + pair as usize * 2) % BODY_RUN_FRAMES;
+
+                    (RUN_STRIDE[step], RUN_LIFT[step])
+                }
+                None => (0, false),
             };
 
-            let col_bytes: Vec<u8> = col.into();
+            // Lifted feet still leave a pixel of leg under the hip.
+            let foot = if lifted { ground - 1 } else { ground }.max(hip + 1);
+            let length = (foot - hip).max(1);
 
-            for k in 0..4 {
-                data[(j * GREACHER_CANVAS_SIZE + i) * 4 + k] = col_bytes[k];
+            for y in (hip + 1)..=foot {
+                let x = hip_x + stride * (y - hip) / length;
+                atlas.set(frame, x, y, ColorMapping::Basic);
             }
         }
     }
 
+    if let Some(tail) = shape.tail {
+        let wag = run_frame.map_or(0, |run_frame| (run_frame as isize / 2) % 2);
+
+        for i in 1..=tail {
+            atlas.set(
+                frame,
+                min_x - i,
+                top + 1 + wag - (i - 1),
+                ColorMapping::Basic,
+            );
+        }
+    }
+}
+
+/// Lays the frames out side by side, in a single row.
+fn create_atlas_data(frames: &[HeadCanvas], palette: &GreacherColorPalette) -> Vec<u8> {
+    let size = frames.first().map_or(0, |frame| frame.size);
+    let row_length = size * 4;
+
+    let frames: Vec<Vec<u8>> = frames
+        .iter()
+        .map(|frame| create_color_data(frame, palette))
+        .collect();
+
+    let mut data = Vec::with_capacity(row_length * size * frames.len());
+
+    for row in 0..size {
+        for frame in &frames {
+            data.extend_from_slice(&frame[row * row_length..(row + 1) * row_length]);
+        }
+    }
+
     data
 }
+
+fn create_color_data(canvas: &HeadCanvas, palette: &GreacherColorPalette) -> Vec<u8> {
+    Color::to_pixels(canvas.data.iter().map(|mapping| mapping.color(palette)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LARGEST_CANVAS: usize = 32;
+    const SEEDS: u64 = 200;
+
+    // Heads have to generate at every canvas size, not just the one the game
+    // uses, since the shapes and patterns are scaled to fit.
+    #[test]
+    fn heads_generate_at_every_canvas_size() {
+        let palette = GreacherColorPalette::default();
+
+        for size in MIN_CANVAS_SIZE..=LARGEST_CANVAS {
+            for version in GeneratorVersion::ALL {
+                for seed in 0..SEEDS {
+                    // Extra eyes is the only trait that changes how heads are drawn.
+                    for traits in [GreacherTraits::none(), GreacherTraits::all()] {
+                        let mut head = create_head_image(size);
+
+                        generate_greacher_head_texture(
+                            &Genome::from_seed(seed),
+                            &mut head,
+                            &palette,
+                            traits,
+                            version,
+                        );
+
+                        assert!(
+                            head.data.iter().any(|byte| *byte != 0),
+                            "Seed {} drew nothing on a {} pixel canvas with {:?}",
+                            seed,
+                            size,
+                            version
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
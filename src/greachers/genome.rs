@@ -0,0 +1,251 @@
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use super::components::GreacherParts;
+
+/// The heritable part of a greacher.
+///
+/// Every trait is rolled from its own gene, so changing one of them doesn't
+/// shift the random stream of the others.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Genome {
+    pub seed: u64,
+    pub head_size: u64,
+    pub head_shape: u64,
+    pub head_pattern: u64,
+    pub eye_size: u64,
+    pub eye_position: u64,
+    pub palette: u64,
+    pub body_type: u64,
+    pub stats: u64,
+    pub name_start: u64,
+    pub name_end: u64,
+    pub rarity: u64,
+    pub rare_traits: u64,
+    pub body_shape: u64,
+    pub size: u64,
+}
+
+impl Genome {
+    pub fn from_seed(seed: u64) -> Genome {
+        let mut rng = SmallRng::seed_from_u64(seed);
+
+        Genome {
+            seed,
+            head_size: rng.gen(),
+            head_shape: rng.gen(),
+            head_pattern: rng.gen(),
+            eye_size: rng.gen(),
+            eye_position: rng.gen(),
+            palette: rng.gen(),
+            body_type: rng.gen(),
+            stats: rng.gen(),
+            name_start: rng.gen(),
+            name_end: rng.gen(),
+            rarity: rng.gen(),
+            rare_traits: rng.gen(),
+            body_shape: rng.gen(),
+            size: rng.gen(),
+        }
+    }
+
+    /// Picks every gene from one of the parents at random. Each gene is
+    /// instead rerolled with a `mutation_rate` chance.
+    pub fn crossover(a: &Genome, b: &Genome, mutation_rate: f32, rng: &mut impl Rng) -> Genome {
+        let seed = rng.gen();
+
+        let mut inherit = |a: u64, b: u64| {
+            if rng.gen::<f32>() < mutation_rate {
+                rng.gen()
+            } else if rng.gen() {
+                a
+            } else {
+                b
+            }
+        };
+
+        Genome {
+            seed,
+            head_size: inherit(a.head_size, b.head_size),
+            head_shape: inherit(a.head_shape, b.head_shape),
+            head_pattern: inherit(a.head_pattern, b.head_pattern),
+            eye_size: inherit(a.eye_size, b.eye_size),
+            eye_position: inherit(a.eye_position, b.eye_position),
+            palette: inherit(a.palette, b.palette),
+            body_type: inherit(a.body_type, b.body_type),
+            stats: inherit(a.stats, b.stats),
+            name_start: inherit(a.name_start, b.name_start),
+            name_end: inherit(a.name_end, b.name_end),
+            rarity: inherit(a.rarity, b.rarity),
+            rare_traits: inherit(a.rare_traits, b.rare_traits),
+            body_shape: inherit(a.body_shape, b.body_shape),
+            size: inherit(a.size, b.size),
+        }
+    }
+
+    /// Rerolls the genes behind the given parts, each part from a fresh
+    /// sub-seed, and keeps the rest. The seed stays the same, so it no longer
+    /// reproduces the genome.
+    pub fn reroll_parts(&mut self, parts: GreacherParts, rng: &mut impl Rng) {
+        for (part, genes) in self.part_genes_mut() {
+            if parts.contains(part) {
+                let mut part_rng = SmallRng::seed_from_u64(rng.gen());
+
+                for gene in genes {
+                    *gene = part_rng.gen();
+                }
+            }
+        }
+    }
+
+    /// The genes each part is generated from. Rarity genes don't belong to
+    /// any, so rerolling never changes a greacher's tier.
+    fn part_genes_mut(&mut self) -> [(GreacherParts, Vec<&mut u64>); 5] {
+        [
+            (
+                GreacherParts::Head,
+                vec![
+                    &mut self.head_size,
+                    &mut self.head_shape,
+                    &mut self.head_pattern,
+                    &mut self.eye_size,
+                    &mut self.eye_position,
+                ],
+            ),
+            (
+                GreacherParts::Body,
+                vec![&mut self.body_type, &mut self.body_shape, &mut self.size],
+            ),
+            (GreacherParts::Stats, vec![&mut self.stats]),
+            (
+                GreacherParts::Name,
+                vec![&mut self.name_start, &mut self.name_end],
+            ),
+            (GreacherParts::Palette, vec![&mut self.palette]),
+        ]
+    }
+}
+
+/// Creates the random stream a single gene drives.
+pub fn gene_rng(gene: u64) -> SmallRng {
+    SmallRng::seed_from_u64(gene)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::SmallRng;
+
+    use super::*;
+
+    fn genes(genome: &Genome) -> [u64; 14] {
+        [
+            genome.head_size,
+            genome.head_shape,
+            genome.head_pattern,
+            genome.eye_size,
+            genome.eye_position,
+            genome.palette,
+            genome.body_type,
+            genome.stats,
+            genome.name_start,
+            genome.name_end,
+            genome.rarity,
+            genome.rare_traits,
+            genome.body_shape,
+            genome.size,
+        ]
+    }
+
+    #[test]
+    fn offspring_inherit_every_gene_from_a_parent() {
+        let a = Genome::from_seed(1);
+        let b = Genome::from_seed(2);
+        let mut rng = SmallRng::seed_from_u64(0);
+        let mut from_a = [false; 14];
+        let mut from_b = [false; 14];
+
+        for _ in 0..100 {
+            let child = Genome::crossover(&a, &b, 0., &mut rng);
+
+            assert_ne!(child.seed, a.seed);
+            assert_ne!(child.seed, b.seed);
+
+            for (i, ((child, a), b)) in genes(&child)
+                .into_iter()
+                .zip(genes(&a))
+                .zip(genes(&b))
+                .enumerate()
+            {
+                assert!(child == a || child == b);
+                from_a[i] |= child == a;
+                from_b[i] |= child == b;
+            }
+        }
+
+        // Both parents should have passed on each gene at least once.
+        assert_eq!(from_a, [true; 14]);
+        assert_eq!(from_b, [true; 14]);
+    }
+
+    #[test]
+    fn mutated_offspring_inherit_nothing() {
+        let a = Genome::from_seed(1);
+        let b = Genome::from_seed(2);
+        let child = Genome::crossover(&a, &b, 1., &mut SmallRng::seed_from_u64(0));
+
+        for ((child, a), b) in genes(&child).into_iter().zip(genes(&a)).zip(genes(&b)) {
+            assert!(child != a && child != b);
+        }
+    }
+
+    #[test]
+    fn rerolling_keeps_other_parts() {
+        let original = Genome::from_seed(42);
+        let parts = [
+            GreacherParts::Head,
+            GreacherParts::Body,
+            GreacherParts::Stats,
+            GreacherParts::Name,
+            GreacherParts::Palette,
+        ];
+
+        for rerolled in parts {
+            let mut genome = original;
+            genome.reroll_parts(rerolled, &mut SmallRng::seed_from_u64(0));
+
+            assert_eq!(genome.seed, original.seed);
+            assert_eq!(genome.rarity, original.rarity);
+            assert_eq!(genome.rare_traits, original.rare_traits);
+
+            let mut original = original;
+            for ((part, new), (_, old)) in genome
+                .part_genes_mut()
+                .into_iter()
+                .zip(original.part_genes_mut())
+            {
+                for (new, old) in new.into_iter().zip(old) {
+                    assert_eq!(*new == *old, part != rerolled, "{:?}", part);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn body_rerolls_cover_size() {
+        let original = Genome::from_seed(42);
+        let mut genome = original;
+        genome.reroll_parts(GreacherParts::Body, &mut SmallRng::seed_from_u64(0));
+
+        assert_ne!(genome.body_type, original.body_type);
+        assert_ne!(genome.body_shape, original.body_shape);
+        assert_ne!(genome.size, original.size);
+    }
+
+    #[test]
+    fn rerolling_nothing_changes_nothing() {
+        let original = Genome::from_seed(42);
+        let mut genome = original;
+        genome.reroll_parts(GreacherParts::none(), &mut SmallRng::seed_from_u64(0));
+
+        assert_eq!(genome, original);
+    }
+}
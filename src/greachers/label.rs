@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+
+use super::{components::Greacher, game_plugin::WorldMouse};
+
+/// Text next to the cursor naming the greacher under it.
+#[derive(Component)]
+pub struct GreacherLabel;
+
+impl GreacherLabel {
+    /// Gap between the cursor and the label, in screen pixels.
+    const CURSOR_OFFSET: f32 = 12.0;
+}
+
+pub fn spawn_greacher_label(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/04b03.ttf");
+
+    commands
+        .spawn_bundle(
+            TextBundle::from_sections([
+                TextSection::from_style(TextStyle {
+                    font: font.clone(),
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                }),
+                TextSection::from_style(TextStyle {
+                    font,
+                    font_size: 16.0,
+                    color: Color::WHITE,
+                }),
+            ])
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                ..default()
+            }),
+        )
+        .insert(Visibility { is_visible: false })
+        .insert(GreacherLabel);
+}
+
+pub fn update_greacher_label(
+    windows: Res<Windows>,
+    world_mouse: Res<WorldMouse>,
+    greachers: Query<(&Greacher, &Transform)>,
+    mut labels: Query<(&mut Text, &mut Style, &mut Visibility), With<GreacherLabel>>,
+) {
+    let cursor = windows
+        .get_primary()
+        .and_then(|window| window.cursor_position());
+
+    let hovered = greachers
+        .iter()
+        .map(|(greacher, transform)| {
+            let distance = transform.translation.truncate().distance(**world_mouse);
+
+            (greacher, distance)
+        })
+        .filter(|(greacher, distance)| {
+            *distance < Greacher::SIZE * greacher.head_scale() as f32 * greacher.size
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+
+    for (mut text, mut style, mut visibility) in &mut labels {
+        match (hovered, cursor) {
+            (Some((greacher, _)), Some(cursor)) => {
+                text.sections[0].value = format!("{}\n", greacher.name);
+                text.sections[1].value = greacher.rarity.to_string();
+                text.sections[1].style.color = greacher.rarity.color();
+
+                style.position = UiRect {
+                    left: Val::Px(cursor.x + GreacherLabel::CURSOR_OFFSET),
+                    bottom: Val::Px(cursor.y + GreacherLabel::CURSOR_OFFSET),
+                    ..default()
+                };
+                visibility.is_visible = true;
+            }
+            _ => visibility.is_visible = false,
+        }
+    }
+}
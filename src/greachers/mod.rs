@@ -1,4 +1,10 @@
 pub mod behavior;
+pub mod breeding;
+pub mod code;
 pub mod components;
 pub mod game_plugin;
 pub mod gen;
+pub mod genome;
+pub mod label;
+pub mod names;
+pub mod rarity;
@@ -0,0 +1,578 @@
+use std::{collections::HashMap, fmt::Display};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::{BoxedFuture, HashSet},
+};
+use rand::{rngs::SmallRng, Rng};
+use serde::Deserialize;
+
+use crate::util::SliceExt;
+
+use super::components::Greacher;
+
+/// Syllable tables and templates greacher names are built from.
+///
+/// Loaded from `.names.ron` files, see `assets/names/` for examples.
+#[derive(Deserialize, TypeUuid, Clone, Debug)]
+#[uuid = "7b1f3c2e-5d64-4a0b-9e57-3c8f2a6d9b41"]
+pub struct NameLanguage {
+    pub name: String,
+    /// Syllable tables, referred to by name from the templates.
+    #[serde(default)]
+    pub syllables: HashMap<String, Vec<String>>,
+    /// Templates for the start of a name, as lists of syllable table names.
+    #[serde(default)]
+    pub starts: Vec<Vec<String>>,
+    /// Templates for the rest of a name.
+    #[serde(default)]
+    pub ends: Vec<Vec<String>>,
+    #[serde(default)]
+    pub prefixes: Vec<String>,
+    #[serde(default)]
+    pub postfixes: Vec<String>,
+    #[serde(default)]
+    pub prefix_chance: f64,
+    #[serde(default)]
+    pub postfix_chance: f64,
+    /// When set, names are made up by a Markov chain instead of the templates.
+    #[serde(default)]
+    pub markov: Option<MarkovSettings>,
+    #[serde(skip)]
+    chain: Option<MarkovChain>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct MarkovSettings {
+    /// How many previous letters decide the next one.
+    pub order: usize,
+    /// Names only end early when the chain runs out of letters to add, like
+    /// after the last letters of a training word only `order` letters long.
+    pub min_length: usize,
+    pub max_length: usize,
+    /// Words the chain is trained on.
+    pub words: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum NameLanguageError {
+    Parse(ron::Error),
+    NoTemplates,
+    EmptyTemplate,
+    MissingTable(String),
+    EmptyTable(String),
+    BadMarkovSettings,
+    /// A prefix or postfix chance outside `0.0..=1.0`, named by its field.
+    BadChance(&'static str, f64),
+}
+
+impl Display for NameLanguageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NameLanguageError::Parse(why) => write!(f, "Couldn't parse language: {}", why),
+            NameLanguageError::NoTemplates => {
+                f.write_str("Languages need start and end templates, or Markov settings")
+            }
+            NameLanguageError::EmptyTemplate => f.write_str("Templates can't be empty"),
+            NameLanguageError::MissingTable(table) => {
+                write!(f, "Template uses unknown syllable table \"{}\"", table)
+            }
+            NameLanguageError::EmptyTable(table) => {
+                write!(f, "Syllable table \"{}\" is empty", table)
+            }
+            NameLanguageError::BadMarkovSettings => f.write_str(
+                "Markov chains need an order above 0 and up to max_length, a word at least that \
+                 many letters long, and min_length <= max_length",
+            ),
+            NameLanguageError::BadChance(field, chance) => {
+                write!(f, "{} is {}, but has to be between 0 and 1", field, chance)
+            }
+        }
+    }
+}
+
+impl std::error::Error for NameLanguageError {}
+
+impl NameLanguage {
+    pub fn from_ron(bytes: &[u8]) -> Result<NameLanguage, NameLanguageError> {
+        let mut language: NameLanguage =
+            ron::de::from_bytes(bytes).map_err(NameLanguageError::Parse)?;
+
+        language.validate()?;
+        language.chain = language.markov.as_ref().map(MarkovChain::train);
+
+        Ok(language)
+    }
+
+    /// The language compiled into the game, used until the asset ones load.
+    pub fn builtin() -> NameLanguage {
+        NameLanguage::from_ron(include_bytes!("../../assets/names/greachish.names.ron"))
+            .expect("Built-in name language is broken!")
+    }
+
+    fn validate(&self) -> Result<(), NameLanguageError> {
+        for (field, chance) in [
+            ("prefix_chance", self.prefix_chance),
+            ("postfix_chance", self.postfix_chance),
+        ] {
+            if !(0.0..=1.0).contains(&chance) {
+                return Err(NameLanguageError::BadChance(field, chance));
+            }
+        }
+
+        if let Some(markov) = &self.markov {
+            // Shorter words are skipped in training, so the chain would have
+            // nowhere to start.
+            let trainable = markov
+                .words
+                .iter()
+                .any(|word| word.chars().count() >= markov.order);
+
+            if markov.order == 0
+                || markov.order > markov.max_length
+                || !trainable
+                || markov.min_length > markov.max_length
+            {
+                return Err(NameLanguageError::BadMarkovSettings);
+            }
+
+            return Ok(());
+        }
+
+        if self.starts.is_empty() || self.ends.is_empty() {
+            return Err(NameLanguageError::NoTemplates);
+        }
+
+        for template in self.starts.iter().chain(&self.ends) {
+            if template.is_empty() {
+                return Err(NameLanguageError::EmptyTemplate);
+            }
+
+            for table in template {
+                match self.syllables.get(table) {
+                    None => return Err(NameLanguageError::MissingTable(table.clone())),
+                    Some(syllables) if syllables.is_empty() => {
+                        return Err(NameLanguageError::EmptyTable(table.clone()))
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Builds a name whose start comes from `start_rng` and the rest from
+    /// `end_rng`.
+    pub fn generate(&self, start_rng: &mut SmallRng, end_rng: &mut SmallRng) -> String {
+        let mut name = match (&self.chain, &self.markov) {
+            (Some(chain), Some(markov)) => {
+                chain.generate(start_rng, end_rng, markov.min_length, markov.max_length)
+            }
+            _ => {
+                let start = self.fill_template(self.starts.random(start_rng), start_rng);
+                let end = self.fill_template(self.ends.random(end_rng), end_rng);
+
+                start + &end
+            }
+        };
+
+        if !self.prefixes.is_empty() && start_rng.gen_bool(self.prefix_chance) {
+            name = format!("{}{}", self.prefixes.random(start_rng), name);
+        }
+
+        if !self.postfixes.is_empty() && end_rng.gen_bool(self.postfix_chance) {
+            name = format!("{}{}", name, self.postfixes.random(end_rng));
+        }
+
+        name.to_uppercase()
+    }
+
+    fn fill_template(&self, template: &[String], rng: &mut SmallRng) -> String {
+        template
+            .iter()
+            .map(|table| self.syllables[table].random(rng).as_str())
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug)]
+struct MarkovChain {
+    order: usize,
+    /// The first `order` letters of every training word.
+    starts: Vec<String>,
+    /// Letters seen after each run of `order` letters. `None` ends the word.
+    transitions: HashMap<String, Vec<Option<char>>>,
+}
+
+impl MarkovChain {
+    fn train(settings: &MarkovSettings) -> MarkovChain {
+        let mut starts = vec![];
+        let mut transitions: HashMap<String, Vec<Option<char>>> = HashMap::new();
+
+        for word in &settings.words {
+            let letters: Vec<char> = word.to_lowercase().chars().collect();
+
+            if letters.len() < settings.order {
+                continue;
+            }
+
+            starts.push(letters[..settings.order].iter().collect());
+
+            for i in settings.order..=letters.len() {
+                let key: String = letters[(i - settings.order)..i].iter().collect();
+                transitions
+                    .entry(key)
+                    .or_default()
+                    .push(letters.get(i).copied());
+            }
+        }
+
+        MarkovChain {
+            order: settings.order,
+            starts,
+            transitions,
+        }
+    }
+
+    fn generate(
+        &self,
+        start_rng: &mut SmallRng,
+        end_rng: &mut SmallRng,
+        min_length: usize,
+        max_length: usize,
+    ) -> String {
+        if self.starts.is_empty() {
+            return String::new();
+        }
+
+        let mut letters: Vec<char> = self.starts.random(start_rng).chars().collect();
+
+        while letters.len() < max_length {
+            let key: String = letters[(letters.len() - self.order)..].iter().collect();
+
+            let options = match self.transitions.get(&key) {
+                Some(options) => options,
+                None => break,
+            };
+
+            let next = if letters.len() < min_length {
+                // Too short to end yet, so only pick letters that carry on.
+                let letters: Vec<char> = options.iter().flatten().copied().collect();
+
+                if letters.is_empty() {
+                    break;
+                }
+
+                Some(*letters.random(end_rng))
+            } else {
+                *options.random(end_rng)
+            };
+
+            match next {
+                Some(letter) => letters.push(letter),
+                None => break,
+            }
+        }
+
+        letters.into_iter().collect()
+    }
+}
+
+#[derive(Default)]
+pub struct NameLanguageLoader;
+
+impl AssetLoader for NameLanguageLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let language = NameLanguage::from_ron(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(language));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["names.ron"]
+    }
+}
+
+/// The name languages players can pick from, and the one new greachers use.
+pub struct NameLanguages {
+    builtin: NameLanguage,
+    pub languages: Vec<Handle<NameLanguage>>,
+    pub selected: usize,
+}
+
+impl NameLanguages {
+    pub const PATHS: [&'static str; 3] = [
+        "names/greachish.names.ron",
+        "names/bubbly.names.ron",
+        "names/mossy.names.ron",
+    ];
+
+    /// The selected language, or the built-in one while it's still loading.
+    pub fn current<'a>(&'a self, assets: &'a Assets<NameLanguage>) -> &'a NameLanguage {
+        self.languages
+            .get(self.selected)
+            .and_then(|handle| assets.get(handle))
+            .unwrap_or(&self.builtin)
+    }
+
+    pub fn cycle_language(
+        keys: Res<Input<KeyCode>>,
+        mut name_languages: ResMut<NameLanguages>,
+        assets: Res<Assets<NameLanguage>>,
+    ) {
+        if !keys.just_pressed(KeyCode::L) || name_languages.languages.is_empty() {
+            return;
+        }
+
+        name_languages.selected = (name_languages.selected + 1) % name_languages.languages.len();
+
+        info!(
+            "New greachers will be named in {}",
+            name_languages.current(&assets).name
+        );
+    }
+}
+
+impl FromWorld for NameLanguages {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+
+        NameLanguages {
+            builtin: NameLanguage::builtin(),
+            languages: NameLanguages::PATHS
+                .iter()
+                .map(|path| asset_server.load(*path))
+                .collect(),
+            selected: 0,
+        }
+    }
+}
+
+/// Names of the greachers currently alive, so no two of them share one.
+#[derive(Default)]
+pub struct NameRegistry {
+    taken: HashSet<String>,
+    by_entity: bevy::utils::HashMap<Entity, String>,
+}
+
+impl NameRegistry {
+    /// Reserves `name` for the entity, with a numeral added after it if it's
+    /// already taken. Returns the name that was reserved.
+    pub fn claim(&mut self, entity: Entity, name: &str) -> String {
+        let mut unique = name.to_string();
+        let mut count = 1;
+
+        while self.taken.contains(&unique) {
+            count += 1;
+            unique = format!("{} {}", name, to_roman(count));
+        }
+
+        self.taken.insert(unique.clone());
+        self.by_entity.insert(entity, unique.clone());
+
+        unique
+    }
+
+    pub fn release_removed(
+        mut registry: ResMut<NameRegistry>,
+        removed: RemovedComponents<Greacher>,
+    ) {
+        for entity in removed.iter() {
+            registry.release(entity);
+        }
+    }
+
+    /// Frees the name of the entity for others to claim.
+    pub fn release(&mut self, entity: Entity) {
+        if let Some(name) = self.by_entity.remove(&entity) {
+            self.taken.remove(&name);
+        }
+    }
+}
+
+fn to_roman(mut number: usize) -> String {
+    const NUMERALS: [(usize, &str); 13] = [
+        (1000, "M"),
+        (900, "CM"),
+        (500, "D"),
+        (400, "CD"),
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+
+    let mut roman = String::new();
+
+    for (value, numeral) in NUMERALS {
+        while number >= value {
+            roman.push_str(numeral);
+            number -= value;
+        }
+    }
+
+    roman
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn markov_language(order: usize, min_length: usize, max_length: usize) -> String {
+        format!(
+            r#"(
+                name: "Test",
+                markov: Some((
+                    order: {},
+                    min_length: {},
+                    max_length: {},
+                    words: ["greacher", "grumble", "bramble", "rumbler", "mumbo"],
+                )),
+            )"#,
+            order, min_length, max_length
+        )
+    }
+
+    #[test]
+    fn roman_numerals() {
+        let cases = [
+            (1, "I"),
+            (2, "II"),
+            (4, "IV"),
+            (9, "IX"),
+            (14, "XIV"),
+            (40, "XL"),
+            (90, "XC"),
+            (400, "CD"),
+            (1994, "MCMXCIV"),
+            (3999, "MMMCMXCIX"),
+        ];
+
+        for (number, roman) in cases {
+            assert_eq!(to_roman(number), roman);
+        }
+    }
+
+    #[test]
+    fn claimed_names_are_unique_until_released() {
+        let mut registry = NameRegistry::default();
+        let [a, b, c] = [1, 2, 3].map(Entity::from_raw);
+
+        assert_eq!(registry.claim(a, "BLOB"), "BLOB");
+        assert_eq!(registry.claim(b, "BLOB"), "BLOB II");
+        assert_eq!(registry.claim(c, "BLOB"), "BLOB III");
+
+        registry.release(a);
+        assert_eq!(registry.claim(a, "BLOB"), "BLOB");
+
+        // Releasing twice, or an entity without a name, does nothing.
+        registry.release(b);
+        registry.release(b);
+        registry.release(Entity::from_raw(4));
+        assert_eq!(registry.claim(b, "BLOB"), "BLOB II");
+        assert_eq!(registry.claim(Entity::from_raw(5), "BLOB"), "BLOB IV");
+    }
+
+    #[test]
+    fn markov_names_keep_to_their_lengths() {
+        let language = NameLanguage::from_ron(markov_language(2, 4, 6).as_bytes()).unwrap();
+
+        for seed in 0..500 {
+            let name = language.generate(
+                &mut SmallRng::seed_from_u64(seed),
+                &mut SmallRng::seed_from_u64(seed + 1),
+            );
+            let length = name.chars().count();
+
+            assert!(
+                (4..=6).contains(&length),
+                "{} is {} letters long",
+                name,
+                length
+            );
+        }
+    }
+
+    #[test]
+    fn markov_names_end_early_at_dead_ends() {
+        // Nothing follows "zo", and "rr" only ever ends a word.
+        let language = NameLanguage::from_ron(
+            br#"(
+                name: "Test",
+                markov: Some((order: 2, min_length: 4, max_length: 6, words: ["grr", "zo"])),
+            )"#,
+        )
+        .unwrap();
+
+        for seed in 0..100 {
+            let name = language.generate(
+                &mut SmallRng::seed_from_u64(seed),
+                &mut SmallRng::seed_from_u64(seed + 1),
+            );
+
+            assert!(name == "GRR" || name == "ZO", "made up {}", name);
+        }
+    }
+
+    #[test]
+    fn markov_settings_need_trainable_words() {
+        for (order, min_length, max_length) in [(0, 1, 5), (9, 1, 12), (3, 1, 2), (2, 6, 4)] {
+            let result =
+                NameLanguage::from_ron(markov_language(order, min_length, max_length).as_bytes());
+
+            assert!(
+                matches!(result, Err(NameLanguageError::BadMarkovSettings)),
+                "order {} and lengths {}..={} were accepted",
+                order,
+                min_length,
+                max_length
+            );
+        }
+    }
+
+    #[test]
+    fn chances_are_probabilities() {
+        for (field, chance) in [
+            ("prefix_chance", "1.5"),
+            ("postfix_chance", "-0.1"),
+            ("prefix_chance", "NaN"),
+        ] {
+            let result = NameLanguage::from_ron(
+                format!(
+                    r#"(
+                        name: "Test",
+                        syllables: {{"a": ["a"]}},
+                        starts: [["a"]],
+                        ends: [["a"]],
+                        {}: {},
+                    )"#,
+                    field, chance
+                )
+                .as_bytes(),
+            );
+
+            assert!(
+                matches!(result, Err(NameLanguageError::BadChance(bad, _)) if bad == field),
+                "{} of {} was accepted",
+                field,
+                chance
+            );
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use bevy::prelude::*;
+use bitmask_enum::bitmask;
+use rand::{rngs::SmallRng, seq::SliceRandom};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum GreacherRarity {
+    Common,
+    Uncommon,
+    Rare,
+    Legendary,
+}
+
+/// Special generation options only rarer greachers can roll.
+#[bitmask(u8)]
+pub enum GreacherTraits {
+    /// Colors slowly cycle through the rainbow.
+    Shimmering,
+    /// A second, smaller pair of eyes.
+    ExtraEyes,
+    /// A title from [`TITLES`] after the name.
+    UniquePostfix,
+    /// The head is drawn on a canvas twice the usual size.
+    DoubleHead,
+}
+
+/// Titles only greachers with [`GreacherTraits::UniquePostfix`] get.
+pub const TITLES: [&str; 10] = [
+    "THE GREAT",
+    "THE ELDER",
+    "THE BRIGHT",
+    "THE UNSEEN",
+    "THE FIRST",
+    "THE LOUD",
+    "THE GLEAMING",
+    "OF THE DEEP",
+    "OF THE MOSS",
+    "OF MANY EYES",
+];
+
+impl GreacherRarity {
+    pub const ALL: [GreacherRarity; 4] = [
+        GreacherRarity::Common,
+        GreacherRarity::Uncommon,
+        GreacherRarity::Rare,
+        GreacherRarity::Legendary,
+    ];
+
+    /// Color the tier is shown in.
+    pub fn color(&self) -> Color {
+        match self {
+            GreacherRarity::Common => Color::rgb_u8(200, 200, 200),
+            GreacherRarity::Uncommon => Color::rgb_u8(96, 220, 96),
+            GreacherRarity::Rare => Color::rgb_u8(80, 150, 255),
+            GreacherRarity::Legendary => Color::GOLD,
+        }
+    }
+
+    /// Rolls the special traits of a greacher of this tier. Uncommon ones get
+    /// one of the subtle traits, rare ones one of any, and legendary ones
+    /// always shimmer on top of one other trait.
+    pub fn roll_traits(&self, rng: &mut SmallRng) -> GreacherTraits {
+        const SUBTLE: [GreacherTraits; 2] =
+            [GreacherTraits::ExtraEyes, GreacherTraits::UniquePostfix];
+        const ANY: [GreacherTraits; 4] = [
+            GreacherTraits::ExtraEyes,
+            GreacherTraits::UniquePostfix,
+            GreacherTraits::DoubleHead,
+            GreacherTraits::Shimmering,
+        ];
+        const NOT_SHIMMERING: [GreacherTraits; 3] = [
+            GreacherTraits::ExtraEyes,
+            GreacherTraits::UniquePostfix,
+            GreacherTraits::DoubleHead,
+        ];
+
+        match self {
+            GreacherRarity::Common => GreacherTraits::none(),
+            GreacherRarity::Uncommon => *SUBTLE.choose(rng).unwrap(),
+            GreacherRarity::Rare => *ANY.choose(rng).unwrap(),
+            GreacherRarity::Legendary => {
+                GreacherTraits::Shimmering | *NOT_SHIMMERING.choose(rng).unwrap()
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for GreacherRarity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            GreacherRarity::Common => "Common",
+            GreacherRarity::Uncommon => "Uncommon",
+            GreacherRarity::Rare => "Rare",
+            GreacherRarity::Legendary => "Legendary",
+        })
+    }
+}
+
+/// Relative chances of a new greacher landing in each tier.
+#[derive(Clone, Copy, Debug)]
+pub struct RarityWeights {
+    pub common: u32,
+    pub uncommon: u32,
+    pub rare: u32,
+    pub legendary: u32,
+}
+
+impl Default for RarityWeights {
+    fn default() -> Self {
+        RarityWeights {
+            common: 700,
+            uncommon: 220,
+            rare: 70,
+            legendary: 10,
+        }
+    }
+}
+
+impl RarityWeights {
+    /// Picks a tier. Everything is common if all the weights are zero.
+    pub fn roll(&self, rng: &mut SmallRng) -> GreacherRarity {
+        let weights = [self.common, self.uncommon, self.rare, self.legendary];
+
+        *GreacherRarity::ALL
+            .iter()
+            .zip(weights)
+            .collect::<Vec<_>>()
+            .choose_weighted(rng, |(_, weight)| *weight)
+            .map(|(rarity, _)| *rarity)
+            .unwrap_or(&GreacherRarity::Common)
+    }
+}
+
+/// Makes a greacher and its body cycle through tinted colors.
+#[derive(Component, Default)]
+pub struct GreacherShimmer {
+    pub phase: f32,
+}
+
+impl GreacherShimmer {
+    /// Hue change per second, in degrees.
+    pub const SPEED: f32 = 90.0;
+}
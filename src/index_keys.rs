@@ -0,0 +1,271 @@
+use std::{
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::{color::Color, palettes::PaletteLoadErrors};
+
+/// The key colors an indexed image is drawn in, one for each slot of a
+/// palette from dark to light. They're read from a `.keys.ron` file next to
+/// the image, so `legs.png` has `legs.keys.ron`. Colors of the image that
+/// aren't keys, like outlines and eye whites, are never recolored.
+///
+/// Palettes with a different number of colors than there are keys are
+/// stretched or squashed to fit, see [`GreacherColorPalette::ramp`].
+///
+/// [`GreacherColorPalette::ramp`]: crate::color::GreacherColorPalette::ramp
+#[derive(TypeUuid, Clone, Debug, PartialEq, Eq)]
+#[uuid = "b84f2d16-7a3c-4e09-91d5-0c6e3f8a2b47"]
+pub struct IndexKeys {
+    pub keys: Vec<Color>,
+}
+
+impl IndexKeys {
+    /// The most keys an image can have, which is what the palette swap shader
+    /// has room for.
+    pub const MAX: usize = 16;
+
+    /// The keys of images without a `.keys.ron` file: blue, green, red and
+    /// yellow for the dark, darkish, basic and highlight colors.
+    pub const CLASSIC: [Color; 4] = [
+        Color::new(0, 0, 255, 255),
+        Color::new(0, 255, 0, 255),
+        Color::new(255, 0, 0, 255),
+        Color::new(255, 255, 0, 255),
+    ];
+
+    pub const EXTENSION: &'static str = "keys.ron";
+
+    /// Where the keys of the image at `image_path` are.
+    pub fn path_for(image_path: &Path) -> PathBuf {
+        image_path.with_extension(Self::EXTENSION)
+    }
+
+    /// Which slot `color` is the key of, if any.
+    pub fn slot(&self, color: Color) -> Option<usize> {
+        self.keys.iter().position(|key| *key == color)
+    }
+
+    pub fn from_ron(bytes: &[u8]) -> Result<IndexKeys, IndexKeysError> {
+        let file: RonIndexKeys = ron::de::from_bytes(bytes).map_err(IndexKeysError::Ron)?;
+
+        if file.keys.is_empty() {
+            return Err(IndexKeysError::NoKeys);
+        }
+
+        if file.keys.len() > Self::MAX {
+            return Err(IndexKeysError::TooManyKeys(file.keys.len()));
+        }
+
+        let mut keys: Vec<Color> = vec![];
+
+        for (slot, hex) in file.keys.iter().enumerate() {
+            let key = hex.parse().ok().filter(Color::is_opaque).ok_or_else(|| {
+                IndexKeysError::BadKey {
+                    slot: slot + 1,
+                    text: hex.clone(),
+                }
+            })?;
+
+            if let Some(first) = keys.iter().position(|other| *other == key) {
+                return Err(IndexKeysError::DuplicateKey {
+                    slots: (first + 1, slot + 1),
+                    text: hex.clone(),
+                });
+            }
+
+            keys.push(key);
+        }
+
+        Ok(IndexKeys { keys })
+    }
+}
+
+impl Default for IndexKeys {
+    fn default() -> Self {
+        IndexKeys {
+            keys: Self::CLASSIC.to_vec(),
+        }
+    }
+}
+
+/// How keys are written in `.keys.ron` files.
+#[derive(Deserialize)]
+struct RonIndexKeys {
+    /// Opaque hex colors, from the darkest slot to the lightest.
+    keys: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum IndexKeysError {
+    NoKeys,
+    TooManyKeys(usize),
+    /// Slots count from 1, like the locations of palette errors.
+    BadKey {
+        slot: usize,
+        text: String,
+    },
+    DuplicateKey {
+        slots: (usize, usize),
+        text: String,
+    },
+    Ron(ron::Error),
+}
+
+impl Display for IndexKeysError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IndexKeysError::NoKeys => f.write_str("There are no keys"),
+            IndexKeysError::TooManyKeys(keys) => write!(
+                f,
+                "Images can have up to {} keys, this one has {}",
+                IndexKeys::MAX,
+                keys
+            ),
+            IndexKeysError::BadKey { slot, text } => write!(
+                f,
+                "Couldn't read key \"{}\" of slot {}, keys are opaque hex colors",
+                text, slot
+            ),
+            IndexKeysError::DuplicateKey { slots, text } => write!(
+                f,
+                "Slots {} and {} have the same key \"{}\"",
+                slots.0, slots.1, text
+            ),
+            IndexKeysError::Ron(why) => write!(f, "Couldn't parse keys: {}", why),
+        }
+    }
+}
+
+impl std::error::Error for IndexKeysError {}
+
+pub struct IndexKeysLoader {
+    errors: PaletteLoadErrors,
+}
+
+impl IndexKeysLoader {
+    pub fn new(errors: PaletteLoadErrors) -> Self {
+        IndexKeysLoader { errors }
+    }
+}
+
+impl AssetLoader for IndexKeysLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            match IndexKeys::from_ron(bytes) {
+                Ok(keys) => {
+                    load_context.set_default_asset(LoadedAsset::new(keys));
+                    Ok(())
+                }
+                Err(why) => {
+                    self.errors.push(format!(
+                        "Couldn't load {}: {}. Using the classic keys.",
+                        load_context.path().display(),
+                        why
+                    ));
+
+                    Err(why.into())
+                }
+            }
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &[IndexKeys::EXTENSION]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(keys: &[&str]) -> Result<IndexKeys, IndexKeysError> {
+        let quoted: Vec<String> = keys.iter().map(|key| format!("\"{}\"", key)).collect();
+
+        IndexKeys::from_ron(format!("(keys: [{}])", quoted.join(", ")).as_bytes())
+    }
+
+    #[test]
+    fn keys_read_in_slot_order() {
+        let read = keys(&["#0000ff", "00ff00", "#FF0000", "#ffff00ff"]).unwrap();
+
+        assert_eq!(read, IndexKeys::default());
+        assert_eq!(read.slot(Color::new(255, 0, 0, 255)), Some(2));
+        assert_eq!(read.slot(Color::new(0, 0, 0, 255)), None);
+
+        let bundled = IndexKeys::from_ron(include_bytes!("../assets/indexed/legs.keys.ron"));
+        assert_eq!(bundled.unwrap(), IndexKeys::default());
+    }
+
+    #[test]
+    fn key_count_errors() {
+        assert!(matches!(keys(&[]), Err(IndexKeysError::NoKeys)));
+
+        let too_many: Vec<String> = (0..=IndexKeys::MAX)
+            .map(|slot| format!("#0000{:02x}", slot))
+            .collect();
+        let too_many: Vec<&str> = too_many.iter().map(String::as_str).collect();
+        let error = keys(&too_many).unwrap_err();
+
+        assert!(matches!(error, IndexKeysError::TooManyKeys(17)));
+        assert_eq!(
+            error.to_string(),
+            "Images can have up to 16 keys, this one has 17"
+        );
+
+        assert!(matches!(
+            IndexKeys::from_ron(b"(keys: \"#000000\")"),
+            Err(IndexKeysError::Ron(_))
+        ));
+    }
+
+    #[test]
+    fn bad_keys_name_their_slot() {
+        for (bad, text) in [
+            (["#000000", "blue"], "blue"),
+            (["#000000", "#0000ff80"], "#0000ff80"),
+            (["#000000", "#12345"], "#12345"),
+        ] {
+            let error = keys(&bad).unwrap_err();
+
+            assert!(
+                matches!(&error, IndexKeysError::BadKey { slot: 2, text: read } if read == text),
+                "{} read as {:?}",
+                text,
+                error
+            );
+            assert_eq!(
+                error.to_string(),
+                format!(
+                    "Couldn't read key \"{}\" of slot 2, keys are opaque hex colors",
+                    text
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn duplicate_keys_name_both_slots() {
+        let error = keys(&["#ff0000", "#00ff00", "#FF0000"]).unwrap_err();
+
+        assert!(matches!(
+            &error,
+            IndexKeysError::DuplicateKey { slots: (1, 3), text } if text == "#FF0000"
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Slots 1 and 3 have the same key \"#FF0000\""
+        );
+    }
+}
@@ -0,0 +1,11 @@
+pub mod basics;
+pub mod camera;
+pub mod color;
+pub mod fps_counter;
+pub mod greachers;
+pub mod index_keys;
+pub mod messages;
+pub mod palette_swap;
+pub mod palettes;
+pub mod states;
+pub mod util;
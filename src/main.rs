@@ -1,4 +1,5 @@
 use bevy::{
+    asset::AssetServerSettings,
     prelude::*,
     render::{
         render_resource::{AddressMode, FilterMode, SamplerDescriptor},
@@ -7,19 +8,14 @@ use bevy::{
     window::WindowMode,
 };
 use bevy_rapier2d::prelude::{NoUserData, RapierPhysicsPlugin};
-use camera::CameraPlugin;
-use color::{IndexerPlugin, GreacherPalettes};
-use fps_counter::FpsCounterPlugin;
-use greachers::game_plugin::GreacherGamePlugin;
-use states::AppState;
-
-mod basics;
-mod camera;
-mod color;
-mod fps_counter;
-mod greachers;
-mod states;
-mod util;
+use greachers_survival::{
+    camera::CameraPlugin,
+    color::{GreacherPalettes, IndexerPlugin},
+    fps_counter::FpsCounterPlugin,
+    greachers::game_plugin::GreacherGamePlugin,
+    messages::MessagePlugin,
+    states::AppState,
+};
 
 fn main() {
     App::new()
@@ -45,9 +41,15 @@ fn main() {
                 ..Default::default()
             },
         })
+        // Palettes and indexed sheets are read again when they're edited.
+        .insert_resource(AssetServerSettings {
+            watch_for_changes: true,
+            ..Default::default()
+        })
         .insert_resource(GreacherPalettes::default())
         .add_plugins(DefaultPlugins)
         .add_state(AppState::Loading)
+        .add_plugin(MessagePlugin)
         .add_plugin(IndexerPlugin)
         .add_plugin(FpsCounterPlugin)
         .add_plugin(CameraPlugin)
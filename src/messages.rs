@@ -0,0 +1,81 @@
+use bevy::prelude::*;
+
+pub struct MessagePlugin;
+
+impl Plugin for MessagePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ShowMessage>()
+            .add_startup_system(spawn_message_text)
+            .add_system(show_messages);
+    }
+}
+
+/// Shows a message at the top of the screen for a while, for problems the
+/// player can do something about, like a broken asset file.
+pub struct ShowMessage(pub String);
+
+#[derive(Component)]
+struct MessageText {
+    timer: Timer,
+}
+
+impl MessageText {
+    /// How long a message stays up after the last one arrived, in seconds.
+    const DURATION: f32 = 8.0;
+}
+
+fn spawn_message_text(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/04b03.ttf"),
+                    font_size: 8.0,
+                    color: Color::rgb(1.0, 0.4, 0.4),
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    left: Val::Px(4.0),
+                    top: Val::Px(4.0),
+                    ..default()
+                },
+                max_size: Size::new(Val::Px(312.0), Val::Undefined),
+                ..default()
+            }),
+        )
+        .insert(Visibility { is_visible: false })
+        .insert(MessageText {
+            timer: Timer::from_seconds(MessageText::DURATION, false),
+        });
+}
+
+fn show_messages(
+    time: Res<Time>,
+    mut events: EventReader<ShowMessage>,
+    mut texts: Query<(&mut Text, &mut Visibility, &mut MessageText)>,
+) {
+    let messages: Vec<&str> = events.iter().map(|message| message.0.as_str()).collect();
+
+    for (mut text, mut visibility, mut message_text) in &mut texts {
+        for message in &messages {
+            if visibility.is_visible {
+                text.sections[0].value.push('\n');
+            } else {
+                text.sections[0].value.clear();
+            }
+
+            text.sections[0].value.push_str(message);
+            visibility.is_visible = true;
+            message_text.timer.reset();
+        }
+
+        message_text.timer.tick(time.delta());
+
+        if message_text.timer.just_finished() {
+            visibility.is_visible = false;
+        }
+    }
+}
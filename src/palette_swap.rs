@@ -0,0 +1,495 @@
+use std::{marker::PhantomData, sync::Arc};
+
+use bevy::{
+    ecs::system::{EntityCommands, SystemParam},
+    prelude::{shape::Quad, *},
+    reflect::TypeUuid,
+    render::{
+        render_resource::{AsBindGroup, Extent3d, ShaderRef, TextureDimension, TextureFormat},
+        RenderApp,
+    },
+    sprite::{Material2d, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle, Rect},
+    utils::{HashMap, HashSet},
+};
+
+use crate::{
+    color::{
+        self, GreacherColorPalette, GreacherPalettes, ImageIndexError, IndexedImageServer,
+        PalettesReloaded,
+    },
+    index_keys::IndexKeys,
+};
+
+/// How indexed sprite sheets get their palettes applied.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteSwapMode {
+    /// Recolored by [`PaletteSwapMaterial`] while drawing, so every palette
+    /// shares the one sheet.
+    Gpu,
+    /// Recolored by the [`IndexedImageServer`], which makes a copy of the
+    /// sheet per palette in use. Used when there's nothing to draw with,
+    /// like in headless runs.
+    Cpu,
+}
+
+/// Registers the palette swap material, or falls back to
+/// [`PaletteSwapMode::Cpu`] without a renderer. Inserting a
+/// [`PaletteSwapMode`] beforehand overrides the choice.
+pub struct PaletteSwapPlugin;
+
+impl Plugin for PaletteSwapPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.world.contains_resource::<PaletteSwapMode>() {
+            let mode = if app.get_sub_app(RenderApp).is_ok() {
+                PaletteSwapMode::Gpu
+            } else {
+                PaletteSwapMode::Cpu
+            };
+
+            app.insert_resource(mode);
+        }
+
+        app.init_resource::<PaletteTexture>()
+            .init_resource::<PaletteSwapFrameCache>();
+
+        if *app.world.resource::<PaletteSwapMode>() == PaletteSwapMode::Gpu {
+            let handle = app
+                .world
+                .resource_mut::<Assets<Image>>()
+                .add(PaletteTexture::image(&[]));
+
+            app.insert_resource(PaletteTexture {
+                handle,
+                rows: vec![],
+            })
+            .add_plugin(Material2dPlugin::<PaletteSwapMaterial>::default())
+            .add_system_to_stage(CoreStage::PostUpdate, update_palette_texture)
+            .add_system_to_stage(CoreStage::PostUpdate, refresh_edited_sheets)
+            .add_system_to_stage(CoreStage::PostUpdate, update_material_keys)
+            .add_system_to_stage(CoreStage::PostUpdate, sync_palette_swapped_sprites);
+        }
+    }
+}
+
+/// Draws an image made of [`IndexKeys`] colors with those replaced by a row
+/// of the palette texture.
+#[derive(AsBindGroup, TypeUuid, Debug, Clone)]
+#[uuid = "6c1d8e0a-3f57-4b9e-9a41-2d7f0c5b8e13"]
+pub struct PaletteSwapMaterial {
+    /// Multiplies the recolored image, like [`TextureAtlasSprite::color`].
+    #[uniform(0)]
+    pub tint: Color,
+    /// The row of the [`PaletteTexture`] to recolor with.
+    #[uniform(0)]
+    pub palette: u32,
+    /// How many colors the palette has, the rest of its row is padding.
+    #[uniform(0)]
+    pub slots: u32,
+    #[uniform(0)]
+    pub key_count: u32,
+    /// The source's keys as sRGB values, only the first `key_count` are used.
+    #[uniform(0)]
+    pub keys: [Vec4; IndexKeys::MAX],
+    #[texture(1)]
+    #[sampler(2)]
+    pub source: Handle<Image>,
+    #[texture(3)]
+    pub palettes: Handle<Image>,
+}
+
+impl PaletteSwapMaterial {
+    /// Marks the material as changed, so it's set up again with the current
+    /// versions of its images. Its bind group keeps the textures it was made
+    /// with otherwise, even after the images are replaced.
+    pub fn refresh(
+        materials: &mut Assets<PaletteSwapMaterial>,
+        handle: &Handle<PaletteSwapMaterial>,
+    ) {
+        materials.get_mut(handle);
+    }
+
+    /// Makes the material recolor `keys`.
+    pub fn set_keys(&mut self, keys: &IndexKeys) {
+        self.key_count = keys.keys.len() as u32;
+        self.keys = [Vec4::ZERO; IndexKeys::MAX];
+
+        for (slot, key) in keys.keys.iter().enumerate() {
+            self.keys[slot] = Color::from(*key).as_rgba_f32().into();
+        }
+    }
+}
+
+impl Material2d for PaletteSwapMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/indexer.wgsl".into()
+    }
+}
+
+/// Every palette sheets were recolored with on the GPU, each as one row of a
+/// texture as wide as the longest palette, the layout the palette swap
+/// shader reads. Shorter rows are padded with transparent pixels.
+///
+/// Rows are only ever added, or edited when their palette is reloaded, so
+/// switching to other palettes doesn't recolor what's already drawn.
+#[derive(Default)]
+pub struct PaletteTexture {
+    pub handle: Handle<Image>,
+    rows: Vec<GreacherColorPalette>,
+}
+
+impl PaletteTexture {
+    pub fn image(rows: &[GreacherColorPalette]) -> Image {
+        let width = rows
+            .iter()
+            .map(|palette| palette.colors.len())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let mut data = color::Color::to_pixels(rows.iter().flat_map(|palette| {
+            let padding = width - palette.colors.len();
+
+            palette
+                .colors
+                .iter()
+                .copied()
+                .chain(std::iter::repeat(color::Color::TRANSPARENT).take(padding))
+        }));
+
+        // Textures can't be empty, so rows of empty palettes are a blank pixel
+        // wide, and a texture without rows is a single one.
+        if data.is_empty() {
+            data = color::Color::to_pixels([color::Color::TRANSPARENT]);
+        }
+
+        Image::new(
+            Extent3d {
+                width: width as u32,
+                height: rows.len().max(1) as u32,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    /// The row holding `palette`, which is added if nothing was drawn with
+    /// it yet.
+    pub fn row(&mut self, palette: &GreacherColorPalette, images: &mut Assets<Image>) -> u32 {
+        let row = match self.rows.iter().position(|row| row == palette) {
+            Some(row) => row,
+            None => {
+                self.rows.push(palette.clone());
+                self.update(images);
+
+                self.rows.len() - 1
+            }
+        };
+
+        row as u32
+    }
+
+    /// Replaced in place, so materials made in the same frame still get the
+    /// new rows. Older ones keep drawing with the texture they were made
+    /// with, which has the same colors in their rows.
+    fn update(&self, images: &mut Assets<Image>) {
+        if let Some(image) = images.get_mut(&self.handle) {
+            *image = PaletteTexture::image(&self.rows);
+        }
+    }
+}
+
+/// Writes reloaded palettes over the rows they used to be, and refreshes the
+/// materials drawing with those.
+fn update_palette_texture(
+    mut reloaded: EventReader<PalettesReloaded>,
+    greacher_palettes: Res<GreacherPalettes>,
+    mut palette_texture: ResMut<PaletteTexture>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<PaletteSwapMaterial>>,
+) {
+    for reloaded in reloaded.iter() {
+        let mut edited = HashSet::new();
+
+        for (row, palette) in palette_texture.rows.iter_mut().enumerate() {
+            let edited_palette = reloaded
+                .previous
+                .iter()
+                .position(|previous| previous == palette)
+                .and_then(|index| greacher_palettes.palettes.get(index));
+
+            match edited_palette {
+                Some(edited_palette) if edited_palette != palette => {
+                    *palette = edited_palette.clone();
+                    edited.insert(row as u32);
+                }
+                _ => {}
+            }
+        }
+
+        if edited.is_empty() {
+            continue;
+        }
+
+        palette_texture.update(&mut images);
+
+        // Palettes can change length when they're edited.
+        let stale: Vec<(Handle<PaletteSwapMaterial>, u32)> = materials
+            .iter()
+            .filter(|(_, material)| edited.contains(&material.palette))
+            .map(|(id, material)| {
+                let slots = palette_texture.rows[material.palette as usize].colors.len();
+
+                (Handle::weak(id), slots as u32)
+            })
+            .collect();
+
+        for (handle, slots) in stale {
+            materials.get_mut(&handle).unwrap().slots = slots;
+        }
+    }
+}
+
+/// Refreshes the materials drawing sheets that were edited on disk.
+fn refresh_edited_sheets(
+    mut events: EventReader<AssetEvent<Image>>,
+    mut materials: ResMut<Assets<PaletteSwapMaterial>>,
+) {
+    let modified: HashSet<Handle<Image>> = events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.clone()),
+            _ => None,
+        })
+        .collect();
+
+    if modified.is_empty() {
+        return;
+    }
+
+    let stale: Vec<Handle<PaletteSwapMaterial>> = materials
+        .iter()
+        .filter(|(_, material)| modified.contains(&material.source))
+        .map(|(id, _)| Handle::weak(id))
+        .collect();
+
+    for handle in stale {
+        PaletteSwapMaterial::refresh(&mut materials, &handle);
+    }
+}
+
+/// Points materials at the keys of their sheets once those load, and again
+/// when they're edited on disk.
+fn update_material_keys(
+    mut events: EventReader<AssetEvent<IndexKeys>>,
+    indexed_server: Res<IndexedImageServer>,
+    index_keys: Res<Assets<IndexKeys>>,
+    mut materials: ResMut<Assets<PaletteSwapMaterial>>,
+) {
+    for event in events.iter() {
+        let handle = match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => handle,
+            AssetEvent::Removed { .. } => continue,
+        };
+
+        let keys = match index_keys.get(handle) {
+            Some(keys) => keys,
+            None => continue,
+        };
+
+        let stale: Vec<Handle<PaletteSwapMaterial>> = materials
+            .iter()
+            .filter(|(_, material)| indexed_server.keys_handle(&material.source) == Some(handle))
+            .map(|(id, _)| Handle::weak(id))
+            .collect();
+
+        for material in stale {
+            materials.get_mut(&material).unwrap().set_keys(keys);
+        }
+    }
+}
+
+/// Quads showing each frame of a sprite sheet, unflipped and then flipped,
+/// shared by everything drawing that sheet.
+#[derive(Component, Clone)]
+pub struct PaletteSwapFrames(Arc<[Mesh2dHandle]>);
+
+impl PaletteSwapFrames {
+    fn new(atlas: &TextureAtlas, meshes: &mut Assets<Mesh>) -> Self {
+        let frames = [false, true]
+            .into_iter()
+            .flat_map(|flip| {
+                atlas
+                    .textures
+                    .iter()
+                    .map(move |rect| Self::frame_mesh(rect, atlas.size, flip))
+            })
+            .map(|mesh| Mesh2dHandle(meshes.add(mesh)))
+            .collect();
+
+        PaletteSwapFrames(frames)
+    }
+
+    fn frame_mesh(rect: &Rect, sheet_size: Vec2, flip: bool) -> Mesh {
+        let min = rect.min / sheet_size;
+        let max = rect.max / sheet_size;
+        let (left, right) = if flip { (max.x, min.x) } else { (min.x, max.x) };
+
+        // Same corner order as the quad's own UVs.
+        let mut mesh = Mesh::from(Quad::new(Vec2::new(rect.width(), rect.height())));
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            vec![[left, max.y], [left, min.y], [right, min.y], [right, max.y]],
+        );
+
+        mesh
+    }
+
+    /// The quad of frame `index`, wrapping around past the last one. Atlases
+    /// without frames have nothing to show.
+    pub fn get(&self, index: usize, flip_x: bool) -> Option<&Mesh2dHandle> {
+        let count = self.0.len() / 2;
+
+        if count == 0 {
+            return None;
+        }
+
+        Some(&self.0[index % count + if flip_x { count } else { 0 }])
+    }
+}
+
+#[derive(Default)]
+pub struct PaletteSwapFrameCache(HashMap<Handle<Image>, PaletteSwapFrames>);
+
+/// Everything needed to draw indexed sprite sheets in either
+/// [`PaletteSwapMode`].
+#[derive(SystemParam)]
+pub struct IndexedSprites<'w, 's> {
+    mode: Res<'w, PaletteSwapMode>,
+    indexed_server: ResMut<'w, IndexedImageServer>,
+    asset_server: Res<'w, AssetServer>,
+    index_keys: Res<'w, Assets<IndexKeys>>,
+    palette_texture: ResMut<'w, PaletteTexture>,
+    frame_cache: ResMut<'w, PaletteSwapFrameCache>,
+    meshes: Option<ResMut<'w, Assets<Mesh>>>,
+    materials: Option<ResMut<'w, Assets<PaletteSwapMaterial>>>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl<'w, 's> IndexedSprites<'w, 's> {
+    /// Makes `entity` draw the sheet of `atlas` recolored with a palette.
+    ///
+    /// Either way the entity is animated through its [`TextureAtlasSprite`],
+    /// which [`sync_palette_swapped_sprites`] copies over to the material
+    /// when recoloring on the GPU.
+    pub fn insert_sheet(
+        &mut self,
+        entity: &mut EntityCommands,
+        atlas: TextureAtlas,
+        palette: &GreacherColorPalette,
+        transform: Transform,
+        texture_atlases: &mut Assets<TextureAtlas>,
+        images: &mut Assets<Image>,
+    ) -> Result<(), ImageIndexError> {
+        match *self.mode {
+            PaletteSwapMode::Gpu => {
+                let meshes = self.meshes.as_mut().expect("Meshes need a renderer");
+                let materials = self.materials.as_mut().expect("Materials need a renderer");
+
+                let frames = self
+                    .frame_cache
+                    .0
+                    .entry(atlas.texture.clone())
+                    .or_insert_with(|| PaletteSwapFrames::new(&atlas, meshes))
+                    .clone();
+
+                let first_frame = frames
+                    .get(0, false)
+                    .ok_or(ImageIndexError::NoFrames)?
+                    .clone();
+
+                let row = self.palette_texture.row(palette, images);
+
+                // Keys still loading are set by `update_material_keys`.
+                let keys = self
+                    .indexed_server
+                    .keys(&atlas.texture, &self.asset_server, &self.index_keys)
+                    .unwrap_or_default();
+
+                let mut material = PaletteSwapMaterial {
+                    tint: Color::WHITE,
+                    palette: row,
+                    slots: palette.colors.len() as u32,
+                    key_count: 0,
+                    keys: [Vec4::ZERO; IndexKeys::MAX],
+                    source: atlas.texture,
+                    palettes: self.palette_texture.handle.clone(),
+                };
+                material.set_keys(&keys);
+
+                entity
+                    .insert_bundle(MaterialMesh2dBundle {
+                        mesh: first_frame,
+                        material: materials.add(material),
+                        transform,
+                        ..Default::default()
+                    })
+                    .insert(TextureAtlasSprite::default())
+                    .insert(frames);
+            }
+            PaletteSwapMode::Cpu => {
+                let texture = self.indexed_server.request_recolored(
+                    atlas.texture.clone(),
+                    palette,
+                    &self.asset_server,
+                    images,
+                    &self.index_keys,
+                )?;
+
+                entity.insert_bundle(SpriteSheetBundle {
+                    texture_atlas: texture_atlases.add(TextureAtlas { texture, ..atlas }),
+                    transform,
+                    ..Default::default()
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Shows the frame and tint palette swapped sprites' [`TextureAtlasSprite`]s
+/// were set to, since those aren't drawn as sprites.
+pub fn sync_palette_swapped_sprites(
+    mut sprites: Query<
+        (
+            &TextureAtlasSprite,
+            &PaletteSwapFrames,
+            &mut Mesh2dHandle,
+            &Handle<PaletteSwapMaterial>,
+        ),
+        Changed<TextureAtlasSprite>,
+    >,
+    mut materials: ResMut<Assets<PaletteSwapMaterial>>,
+) {
+    for (sprite, frames, mut mesh, material_handle) in &mut sprites {
+        let frame = match frames.get(sprite.index, sprite.flip_x) {
+            Some(frame) => frame,
+            None => continue,
+        };
+
+        if mesh.0 != frame.0 {
+            *mesh = frame.clone();
+        }
+
+        let tint_changed = materials
+            .get(material_handle)
+            .is_some_and(|material| material.tint != sprite.color);
+
+        if tint_changed {
+            materials.get_mut(material_handle).unwrap().tint = sprite.color;
+        }
+    }
+}
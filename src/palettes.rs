@@ -0,0 +1,923 @@
+use std::{
+    fmt::Display,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    asset::{Asset, AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    render::render_resource::TextureFormat,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+
+use crate::{
+    color::{Color, GreacherColorPalette, GreacherPalettes, PalettesReloaded},
+    messages::ShowMessage,
+};
+
+/// A named list of palettes, loaded from any of the [`PaletteFormat`]s.
+///
+/// Files without a palette structure of their own are read as a flat list of
+/// colors, every four of them making up a palette from dark to light, like
+/// the rows of `palette.png`. `.gpl` files can set how many colors their
+/// palettes have with their `Columns` header.
+#[derive(TypeUuid, Clone, Debug)]
+#[uuid = "3a9e4c71-0b2d-4f58-8c16-e5d7a2f04b69"]
+pub struct PaletteSet {
+    pub name: String,
+    pub palettes: Vec<GreacherColorPalette>,
+}
+
+/// The palette file formats that can be loaded, picked by extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteFormat {
+    /// GIMP's `.gpl`.
+    Gpl,
+    /// JASC-PAL `.pal`, as written by Paint Shop Pro, Aseprite and others.
+    JascPal,
+    /// `.hex` files with one `RRGGBB` color per line, as on Lospec.
+    Hex,
+    /// `.palette.ron`, see `assets/palettes/` for an example.
+    Ron,
+}
+
+/// Where something is in a palette file, counting from 1. Rows are lines of
+/// text files and pixel rows of images, and in `.palette.ron` files the
+/// palettes in the list.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PaletteLocation {
+    pub row: usize,
+    pub column: usize,
+}
+
+impl PaletteLocation {
+    /// Where `part` starts within `line`, a line of text at index `row`.
+    fn in_line(row: usize, line: &str, part: &str) -> PaletteLocation {
+        let offset = part.as_ptr() as usize - line.as_ptr() as usize;
+
+        PaletteLocation {
+            row: row + 1,
+            column: line[..offset].chars().count() + 1,
+        }
+    }
+
+    /// Just past the end of `line`, a line of text at index `row`.
+    fn line_end(row: usize, line: &str) -> PaletteLocation {
+        PaletteLocation {
+            row: row + 1,
+            column: line.trim_end().chars().count() + 1,
+        }
+    }
+}
+
+impl Display for PaletteLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "row {}, column {}", self.row, self.column)
+    }
+}
+
+#[derive(Debug)]
+pub enum PaletteError {
+    UnknownFormat,
+    NotText,
+    MissingHeader(&'static str),
+    BadColor {
+        at: PaletteLocation,
+        text: String,
+    },
+    BadColorCount {
+        at: PaletteLocation,
+        text: String,
+    },
+    WrongColorCount {
+        at: PaletteLocation,
+        expected: usize,
+        found: usize,
+    },
+    /// A palette with a different number of colors than the rest.
+    PaletteSize {
+        at: PaletteLocation,
+        expected: usize,
+        colors: usize,
+    },
+    EmptyPalette(PaletteLocation),
+    /// A transparent pixel in a palette image, among colors that aren't.
+    TransparentColor(PaletteLocation),
+    /// The file ends before the color count of a JASC-PAL palette.
+    MissingColorCount(PaletteLocation),
+    UnsupportedFormat(TextureFormat),
+    BadImageSize {
+        width: u32,
+        height: u32,
+        bytes: usize,
+    },
+    Empty,
+    TooManyPalettes(usize),
+    Ron(ron::Error),
+}
+
+impl Display for PaletteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PaletteError::UnknownFormat => write!(
+                f,
+                "Palette files have to be images or one of {}",
+                PaletteFormat::EXTENSIONS
+                    .map(|ext| format!(".{}", ext))
+                    .join(", ")
+            ),
+            PaletteError::NotText => f.write_str("Palette files have to be UTF-8 text"),
+            PaletteError::MissingHeader(header) => {
+                write!(f, "Palette doesn't start with \"{}\"", header)
+            }
+            PaletteError::BadColor { at, text } => {
+                write!(f, "Couldn't read color \"{}\" at {}", text, at)
+            }
+            PaletteError::BadColorCount { at, text } => {
+                write!(f, "Couldn't read color count \"{}\" at {}", text, at)
+            }
+            PaletteError::WrongColorCount {
+                at,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Color count at {} says {}, but there are {} colors",
+                at, expected, found
+            ),
+            PaletteError::PaletteSize {
+                at,
+                expected,
+                colors,
+            } => write!(
+                f,
+                "Palettes need {} colors, the one at {} has {}",
+                expected, at, colors
+            ),
+            PaletteError::EmptyPalette(at) => write!(f, "The palette at {} has no colors", at),
+            PaletteError::TransparentColor(at) => write!(
+                f,
+                "The palette at {} is partly transparent, only padding can be",
+                at
+            ),
+            PaletteError::MissingColorCount(at) => {
+                write!(f, "Palette ends at {} before its color count", at)
+            }
+            PaletteError::UnsupportedFormat(format) => write!(
+                f,
+                "Palette images have to be 8 bit RGBA, this one is {:?}",
+                format
+            ),
+            PaletteError::BadImageSize {
+                width,
+                height,
+                bytes,
+            } => write!(
+                f,
+                "Palette image is {}x{} but has {} bytes of pixels",
+                width, height, bytes
+            ),
+            PaletteError::Empty => f.write_str("Palette set has no palettes"),
+            PaletteError::TooManyPalettes(count) => write!(
+                f,
+                "Palette set has {} palettes, but only {} fit in greacher codes",
+                count,
+                GreacherPalettes::MAX_LOADED
+            ),
+            PaletteError::Ron(why) => write!(f, "Couldn't parse palette set: {}", why),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
+
+impl PaletteFormat {
+    pub const EXTENSIONS: [&'static str; 4] = ["gpl", "pal", "hex", "palette.ron"];
+
+    pub fn from_path(path: &Path) -> Option<PaletteFormat> {
+        let file_name = path.file_name()?.to_str()?.to_lowercase();
+
+        if file_name.ends_with(".palette.ron") {
+            return Some(PaletteFormat::Ron);
+        }
+
+        match file_name.rsplit_once('.')?.1 {
+            "gpl" => Some(PaletteFormat::Gpl),
+            "pal" => Some(PaletteFormat::JascPal),
+            "hex" => Some(PaletteFormat::Hex),
+            _ => None,
+        }
+    }
+
+    /// Reads a palette set, named `name` unless the file names it itself.
+    pub fn parse(self, bytes: &[u8], name: &str) -> Result<PaletteSet, PaletteError> {
+        if self == PaletteFormat::Ron {
+            return PaletteSet::from_ron(bytes);
+        }
+
+        let text = std::str::from_utf8(bytes).map_err(|_| PaletteError::NotText)?;
+
+        match self {
+            PaletteFormat::Gpl => PaletteSet::from_gpl(text, name),
+            PaletteFormat::JascPal => PaletteSet::from_jasc_pal(text, name),
+            PaletteFormat::Hex => PaletteSet::from_hex(text, name),
+            PaletteFormat::Ron => unreachable!(),
+        }
+    }
+}
+
+/// How palette sets are written in `.palette.ron` files.
+#[derive(Deserialize)]
+struct RonPaletteSet {
+    name: String,
+    /// Palettes as lists of hex colors from dark to light, usually four.
+    palettes: Vec<Vec<String>>,
+}
+
+impl PaletteSet {
+    /// Reads a palette set from a file of any [`PaletteFormat`], named after
+    /// the file unless it names itself.
+    pub fn from_file(path: &Path, bytes: &[u8]) -> Result<PaletteSet, PaletteError> {
+        let format = PaletteFormat::from_path(path).ok_or(PaletteError::UnknownFormat)?;
+
+        format.parse(bytes, &Self::name_from_path(path))
+    }
+
+    /// The file name up to its first dot, so `dusk.palette.ron` is `dusk`.
+    pub fn name_from_path(path: &Path) -> String {
+        path.file_name()
+            .and_then(|name| name.to_str())
+            .and_then(|name| name.split('.').next())
+            .unwrap_or_default()
+            .to_string()
+    }
+
+    /// Groups a flat list of colors into palettes of `slots` colors, each
+    /// color along with where it was read from.
+    fn from_colors(
+        name: String,
+        colors: Vec<(PaletteLocation, Color)>,
+        slots: usize,
+    ) -> Result<PaletteSet, PaletteError> {
+        let leftover = colors.len() % slots;
+
+        if leftover != 0 {
+            return Err(PaletteError::PaletteSize {
+                at: colors[colors.len() - leftover].0,
+                expected: slots,
+                colors: leftover,
+            });
+        }
+
+        let palettes = colors
+            .chunks_exact(slots)
+            .map(|colors| {
+                GreacherColorPalette::new(colors.iter().map(|(_, color)| *color).collect())
+            })
+            .collect();
+
+        PaletteSet { name, palettes }.validated()
+    }
+
+    pub fn from_gpl(text: &str, name: &str) -> Result<PaletteSet, PaletteError> {
+        let mut lines = text.lines().enumerate();
+
+        if lines.next().map(|(_, line)| line.trim()) != Some("GIMP Palette") {
+            return Err(PaletteError::MissingHeader("GIMP Palette"));
+        }
+
+        let mut name = name.to_string();
+        let mut slots = GreacherColorPalette::CLASSIC_SLOTS;
+        let mut colors = vec![];
+
+        for (row, line) in lines {
+            let trimmed = line.trim();
+
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+
+            // Colors start with a channel, headers with their name.
+            if trimmed.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                match trimmed.split_once(':') {
+                    Some(("Name", value)) => name = value.trim().to_string(),
+                    // Editors show palettes a row each when it's set to their
+                    // length.
+                    Some(("Columns", value)) => {
+                        let value = value.trim();
+
+                        slots = value
+                            .parse()
+                            .ok()
+                            .filter(|&columns| columns > 0)
+                            .ok_or_else(|| PaletteError::BadColorCount {
+                                at: PaletteLocation::in_line(row, line, value),
+                                text: value.to_string(),
+                            })?;
+                    }
+                    // Other headers are just for editors.
+                    _ => {}
+                }
+
+                continue;
+            }
+
+            // Anything after the channels is the color's name.
+            colors.push(parse_channels(row, line)?);
+        }
+
+        PaletteSet::from_colors(name, colors, slots)
+    }
+
+    pub fn from_jasc_pal(text: &str, name: &str) -> Result<PaletteSet, PaletteError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty());
+
+        let header = lines.next();
+
+        if header.map(|(_, line)| line.trim()) != Some("JASC-PAL") {
+            return Err(PaletteError::MissingHeader("JASC-PAL"));
+        }
+
+        // The format version, which has only ever been 0100.
+        let (last_row, last_line) = lines.next().or(header).unwrap();
+
+        let (count_row, count_line) = lines.next().ok_or_else(|| {
+            PaletteError::MissingColorCount(PaletteLocation::line_end(last_row, last_line))
+        })?;
+        let count = count_line.trim();
+        let at = PaletteLocation::in_line(count_row, count_line, count);
+        let expected: usize = count.parse().map_err(|_| PaletteError::BadColorCount {
+            at,
+            text: count.to_string(),
+        })?;
+
+        let colors = lines
+            .map(|(row, line)| parse_channels(row, line))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if colors.len() != expected {
+            return Err(PaletteError::WrongColorCount {
+                at,
+                expected,
+                found: colors.len(),
+            });
+        }
+
+        PaletteSet::from_colors(
+            name.to_string(),
+            colors,
+            GreacherColorPalette::CLASSIC_SLOTS,
+        )
+    }
+
+    pub fn from_hex(text: &str, name: &str) -> Result<PaletteSet, PaletteError> {
+        let colors = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(row, line)| {
+                let hex = line.trim();
+                let at = PaletteLocation::in_line(row, line, hex);
+
+                match hex.parse() {
+                    Ok(color) => Ok((at, color)),
+                    Err(_) => Err(PaletteError::BadColor {
+                        at,
+                        text: hex.to_string(),
+                    }),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        PaletteSet::from_colors(
+            name.to_string(),
+            colors,
+            GreacherColorPalette::CLASSIC_SLOTS,
+        )
+    }
+
+    pub fn from_ron(bytes: &[u8]) -> Result<PaletteSet, PaletteError> {
+        let set: RonPaletteSet = ron::de::from_bytes(bytes).map_err(PaletteError::Ron)?;
+
+        let mut palettes = vec![];
+
+        for (row, palette) in set.palettes.iter().enumerate() {
+            if palette.is_empty() {
+                return Err(PaletteError::EmptyPalette(PaletteLocation {
+                    row: row + 1,
+                    column: 1,
+                }));
+            }
+
+            let mut colors = vec![];
+
+            for (column, hex) in palette.iter().enumerate() {
+                let at = PaletteLocation {
+                    row: row + 1,
+                    column: column + 1,
+                };
+
+                match hex.parse() {
+                    Ok(color) => colors.push(color),
+                    Err(_) => {
+                        return Err(PaletteError::BadColor {
+                            at,
+                            text: hex.clone(),
+                        })
+                    }
+                }
+            }
+
+            palettes.push(GreacherColorPalette::new(colors));
+        }
+
+        PaletteSet {
+            name: set.name,
+            palettes,
+        }
+        .validated()
+    }
+
+    fn validated(self) -> Result<PaletteSet, PaletteError> {
+        if self.palettes.is_empty() {
+            return Err(PaletteError::Empty);
+        }
+
+        if self.palettes.len() > GreacherPalettes::MAX_LOADED {
+            return Err(PaletteError::TooManyPalettes(self.palettes.len()));
+        }
+
+        Ok(self)
+    }
+}
+
+/// Reads a color written as three decimal channels at the start of `line`,
+/// ignoring anything after them.
+fn parse_channels(row: usize, line: &str) -> Result<(PaletteLocation, Color), PaletteError> {
+    let mut parts = line.split_whitespace();
+    let mut channels = [0; 3];
+
+    for channel in &mut channels {
+        let part = parts.next().ok_or_else(|| PaletteError::BadColor {
+            at: PaletteLocation::line_end(row, line),
+            text: line.trim().to_string(),
+        })?;
+
+        *channel = part.parse().map_err(|_| PaletteError::BadColor {
+            at: PaletteLocation::in_line(row, line, part),
+            text: part.to_string(),
+        })?;
+    }
+
+    let [r, g, b] = channels;
+    let start = line.trim_start();
+
+    Ok((
+        PaletteLocation::in_line(row, line, start),
+        Color::new(r, g, b, 255),
+    ))
+}
+
+/// Errors from loading palette sets, kept for showing in game since the
+/// loader runs away from the ECS.
+#[derive(Clone, Default)]
+pub struct PaletteLoadErrors(Arc<Mutex<Vec<String>>>);
+
+impl PaletteLoadErrors {
+    pub fn push(&self, message: String) {
+        self.0.lock().unwrap().push(message);
+    }
+
+    pub fn report(errors: Res<PaletteLoadErrors>, mut messages: EventWriter<ShowMessage>) {
+        for error in errors.0.lock().unwrap().drain(..) {
+            messages.send(ShowMessage(error));
+        }
+    }
+}
+
+pub struct PaletteSetLoader {
+    errors: PaletteLoadErrors,
+}
+
+impl PaletteSetLoader {
+    pub fn new(errors: PaletteLoadErrors) -> Self {
+        PaletteSetLoader { errors }
+    }
+}
+
+impl AssetLoader for PaletteSetLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let path = load_context.path();
+
+            match PaletteSet::from_file(path, bytes) {
+                Ok(set) => {
+                    load_context.set_default_asset(LoadedAsset::new(set));
+                    Ok(())
+                }
+                Err(why) => {
+                    self.errors
+                        .push(format!("Couldn't load {}: {}", path.display(), why));
+
+                    Err(why.into())
+                }
+            }
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &PaletteFormat::EXTENSIONS
+    }
+}
+
+/// The palette sets players can swap between for newly made greachers.
+pub struct PaletteSets {
+    pub sets: Vec<Handle<PaletteSet>>,
+    /// Index into `sets`, or `None` while `palette.png` is used.
+    pub selected: Option<usize>,
+}
+
+impl PaletteSets {
+    pub const PATHS: [&'static str; 4] = [
+        "palettes/classic.palette.ron",
+        "palettes/dusk.gpl",
+        "palettes/candy.pal",
+        "palettes/moss.hex",
+    ];
+
+    /// Moves on to the next loaded set, or back to `palette.png` after the
+    /// last one.
+    pub fn cycle_palette_set(
+        keys: Res<Input<KeyCode>>,
+        mut palette_sets: ResMut<PaletteSets>,
+        assets: Res<Assets<PaletteSet>>,
+        images: Res<Assets<Image>>,
+        mut greacher_palettes: ResMut<GreacherPalettes>,
+    ) {
+        if !keys.just_pressed(KeyCode::P) {
+            return;
+        }
+
+        let start = palette_sets.selected.map_or(0, |selected| selected + 1);
+        let next = (start..palette_sets.sets.len())
+            .find(|&index| assets.contains(&palette_sets.sets[index]));
+
+        let (name, palettes) = match next {
+            Some(index) => {
+                let set = assets.get(&palette_sets.sets[index]).unwrap();
+
+                (set.name.clone(), set.palettes.clone())
+            }
+            None => {
+                let palettes = greacher_palettes
+                    .palette_source
+                    .as_ref()
+                    .and_then(|source| images.get(source))
+                    .and_then(|source| GreacherPalettes::palettes_from_image(source).ok());
+
+                match palettes {
+                    Some(palettes) => (GreacherPalettes::PATH.to_string(), palettes),
+                    None => (
+                        GreacherPalettes::DEFAULT_SET.to_string(),
+                        vec![GreacherColorPalette::default()],
+                    ),
+                }
+            }
+        };
+
+        palette_sets.selected = next;
+
+        let palette_source = greacher_palettes.palette_source.take();
+        info!("New greachers will be colored with the {} palettes", name);
+
+        *greacher_palettes = GreacherPalettes::new(palette_source, name, palettes);
+    }
+
+    /// Reads the palettes in use again when their file is edited, keeping
+    /// the old ones if the edit broke it. Broken palette sets are reported by
+    /// their loader.
+    #[allow(clippy::too_many_arguments)]
+    pub fn reload_palettes(
+        mut image_events: EventReader<AssetEvent<Image>>,
+        mut set_events: EventReader<AssetEvent<PaletteSet>>,
+        palette_sets: Res<PaletteSets>,
+        assets: Res<Assets<PaletteSet>>,
+        images: Res<Assets<Image>>,
+        mut greacher_palettes: ResMut<GreacherPalettes>,
+        mut reloaded: EventWriter<PalettesReloaded>,
+        mut messages: EventWriter<ShowMessage>,
+    ) {
+        let image_modified = modified(&mut image_events, greacher_palettes.palette_source.as_ref());
+        let set_modified = modified(
+            &mut set_events,
+            palette_sets.selected.map(|index| &palette_sets.sets[index]),
+        );
+
+        let (name, palettes) = match palette_sets.selected {
+            Some(index) if set_modified => match assets.get(&palette_sets.sets[index]) {
+                Some(set) => (set.name.clone(), set.palettes.clone()),
+                None => return,
+            },
+            None if image_modified => {
+                let palettes = greacher_palettes
+                    .palette_source
+                    .as_ref()
+                    .and_then(|source| images.get(source))
+                    .map(GreacherPalettes::palettes_from_image);
+
+                match palettes {
+                    Some(Ok(palettes)) => (GreacherPalettes::PATH.to_string(), palettes),
+                    Some(Err(why)) => {
+                        let why = format!("Couldn't read {}: {}", GreacherPalettes::PATH, why);
+
+                        error!("{}, keeping the old palettes", why);
+                        messages.send(ShowMessage(format!("{}. Keeping the old palettes.", why)));
+
+                        return;
+                    }
+                    None => return,
+                }
+            }
+            _ => return,
+        };
+
+        let palette_source = greacher_palettes.palette_source.take();
+        let previous = std::mem::replace(
+            &mut *greacher_palettes,
+            GreacherPalettes::new(palette_source, name, palettes),
+        );
+
+        reloaded.send(PalettesReloaded {
+            previous: previous.palettes,
+        });
+
+        info!("Reloaded {}", greacher_palettes.set);
+    }
+}
+
+/// Whether `handle` was among the assets modified since the last read.
+/// Reads every event either way, so they don't pile up.
+fn modified<T: Asset>(events: &mut EventReader<AssetEvent<T>>, handle: Option<&Handle<T>>) -> bool {
+    let mut found = false;
+
+    for event in events.iter() {
+        found |=
+            matches!(event, AssetEvent::Modified { handle: modified } if Some(modified) == handle);
+    }
+
+    found
+}
+
+impl FromWorld for PaletteSets {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+
+        PaletteSets {
+            sets: PaletteSets::PATHS
+                .iter()
+                .map(|path| asset_server.load(*path))
+                .collect(),
+            selected: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn colors(set: &PaletteSet) -> Vec<Vec<[u8; 4]>> {
+        set.palettes
+            .iter()
+            .map(|palette| palette.colors.iter().map(|&color| color.into()).collect())
+            .collect()
+    }
+
+    const GRAYS: [[u8; 4]; 4] = [
+        [0, 0, 0, 255],
+        [85, 85, 85, 255],
+        [170, 170, 170, 255],
+        [255, 255, 255, 255],
+    ];
+
+    #[test]
+    fn formats_go_by_extension() {
+        let format = |path: &str| PaletteFormat::from_path(Path::new(path));
+
+        assert_eq!(format("a/dusk.gpl"), Some(PaletteFormat::Gpl));
+        assert_eq!(format("CANDY.PAL"), Some(PaletteFormat::JascPal));
+        assert_eq!(format("moss.hex"), Some(PaletteFormat::Hex));
+        assert_eq!(format("classic.palette.ron"), Some(PaletteFormat::Ron));
+        assert_eq!(format("classic.ron"), None);
+        assert_eq!(format("palette.png"), None);
+        assert_eq!(format("gpl"), None);
+
+        assert_eq!(
+            PaletteSet::name_from_path(Path::new("a/dusk.palette.ron")),
+            "dusk"
+        );
+    }
+
+    #[test]
+    fn gpl_palettes() {
+        let set = PaletteSet::from_gpl(
+            "GIMP Palette\n\
+             Name: Grays\n\
+             Columns: 2\n\
+             # Comment\n\
+             \n\
+             0 0 0\tBlack\n\
+             85 85 85\n\
+             \x20 170 170 170 Light gray\n\
+             255 255 255\n",
+            "file",
+        )
+        .unwrap();
+
+        assert_eq!(set.name, "Grays");
+        assert_eq!(colors(&set), [&GRAYS[..2], &GRAYS[2..]]);
+    }
+
+    #[test]
+    fn gpl_palettes_default_to_four_colors() {
+        let set = PaletteSet::from_gpl(
+            "GIMP Palette\n0 0 0\n85 85 85\n170 170 170\n255 255 255",
+            "file",
+        )
+        .unwrap();
+
+        assert_eq!(set.name, "file");
+        assert_eq!(colors(&set), [GRAYS]);
+    }
+
+    #[test]
+    fn jasc_pal_palettes() {
+        let set = PaletteSet::from_jasc_pal(
+            "JASC-PAL\r\n0100\r\n4\r\n0 0 0\r\n85 85 85\r\n170 170 170\r\n255 255 255\r\n",
+            "file",
+        )
+        .unwrap();
+
+        assert_eq!(set.name, "file");
+        assert_eq!(colors(&set), [GRAYS]);
+    }
+
+    #[test]
+    fn hex_palettes() {
+        let set = PaletteSet::from_hex("000000\n555555\n\n#aaaaaa\n  FFFFFF  \n", "file").unwrap();
+
+        assert_eq!(colors(&set), [GRAYS]);
+    }
+
+    #[test]
+    fn ron_palettes() {
+        let set = PaletteSet::from_ron(
+            br##"(
+                name: "Grays",
+                palettes: [
+                    ["000000", "#555555", "aaaaaa", "ffffff"],
+                    ["000000", "ffffff80"],
+                ],
+            )"##,
+        )
+        .unwrap();
+
+        assert_eq!(set.name, "Grays");
+        assert_eq!(
+            colors(&set),
+            [GRAYS.to_vec(), vec![[0, 0, 0, 255], [255, 255, 255, 128]]]
+        );
+    }
+
+    fn at(row: usize, column: usize) -> PaletteLocation {
+        PaletteLocation { row, column }
+    }
+
+    #[test]
+    fn gpl_errors() {
+        let gpl = |text: &str| PaletteSet::from_gpl(text, "file").unwrap_err();
+
+        assert!(matches!(
+            gpl("0 0 0\n"),
+            PaletteError::MissingHeader("GIMP Palette")
+        ));
+        assert!(matches!(
+            gpl("GIMP Palette\nColumns:  many\n"),
+            PaletteError::BadColorCount { at: location, text }
+                if location == at(2, 11) && text == "many"
+        ));
+        assert!(matches!(
+            gpl("GIMP Palette\nColumns: 0\n"),
+            PaletteError::BadColorCount { at: location, .. } if location == at(2, 10)
+        ));
+        assert!(matches!(
+            gpl("GIMP Palette\n0 0 0\n 12 red 3\n"),
+            PaletteError::BadColor { at: location, text } if location == at(3, 5) && text == "red"
+        ));
+        assert!(matches!(
+            gpl("GIMP Palette\n0 0 0\n\n12 34 \n"),
+            PaletteError::BadColor { at: location, .. } if location == at(4, 6)
+        ));
+        assert!(matches!(
+            gpl("GIMP Palette\n1 1 1\n2 2 2\n3 3 3\n4 4 4\n# Comment\n 5 5 5\n6 6 6\n"),
+            PaletteError::PaletteSize { at: location, expected: 4, colors: 2 }
+                if location == at(7, 2)
+        ));
+        assert!(matches!(
+            gpl("GIMP Palette\nName: Nothing\n"),
+            PaletteError::Empty
+        ));
+    }
+
+    #[test]
+    fn jasc_pal_errors() {
+        let pal = |text: &str| PaletteSet::from_jasc_pal(text, "file").unwrap_err();
+
+        assert!(matches!(
+            pal("GIMP Palette\n"),
+            PaletteError::MissingHeader("JASC-PAL")
+        ));
+        assert!(matches!(
+            pal("JASC-PAL"),
+            PaletteError::MissingColorCount(location) if location == at(1, 9)
+        ));
+        assert!(matches!(
+            pal("JASC-PAL\r\n0100 \r\n\r\n"),
+            PaletteError::MissingColorCount(location) if location == at(2, 5)
+        ));
+        assert!(matches!(
+            pal("JASC-PAL\n0100\n\n four\n"),
+            PaletteError::BadColorCount { at: location, text }
+                if location == at(4, 2) && text == "four"
+        ));
+        assert!(matches!(
+            pal("JASC-PAL\n0100\n8\n1 1 1\n2 2 2\n3 3 3\n4 4 4\n"),
+            PaletteError::WrongColorCount { at: location, expected: 8, found: 4 }
+                if location == at(3, 1)
+        ));
+        assert!(matches!(
+            pal("JASC-PAL\n0100\n4\n1 1 1\n2 2 2\n3 3 256\n4 4 4\n"),
+            PaletteError::BadColor { at: location, text } if location == at(6, 5) && text == "256"
+        ));
+        assert!(matches!(
+            pal("JASC-PAL\n0100\n5\n1 1 1\n2 2 2\n3 3 3\n4 4 4\n5 5 5\n"),
+            PaletteError::PaletteSize { at: location, colors: 1, .. } if location == at(8, 1)
+        ));
+        assert!(matches!(pal("JASC-PAL\n0100\n0\n"), PaletteError::Empty));
+    }
+
+    #[test]
+    fn hex_errors() {
+        let hex = |text: &str| PaletteSet::from_hex(text, "file").unwrap_err();
+
+        assert!(matches!(
+            hex("000000\n\n  #12345g\n"),
+            PaletteError::BadColor { at: location, text }
+                if location == at(3, 3) && text == "#12345g"
+        ));
+        assert!(matches!(
+            hex("000000\n555555\n"),
+            PaletteError::PaletteSize { at: location, colors: 2, .. } if location == at(1, 1)
+        ));
+        assert!(matches!(hex("\n\n"), PaletteError::Empty));
+    }
+
+    #[test]
+    fn ron_errors() {
+        let ron = |text: &str| PaletteSet::from_ron(text.as_bytes()).unwrap_err();
+
+        assert!(matches!(ron("(name: \"Broken\")"), PaletteError::Ron(_)));
+        assert!(matches!(
+            ron("(name: \"Gaps\", palettes: [[\"000000\"], []])"),
+            PaletteError::EmptyPalette(location) if location == at(2, 1)
+        ));
+        assert!(matches!(
+            ron("(name: \"Typo\", palettes: [[\"000000\"], [\"000000\", \"00000\"]])"),
+            PaletteError::BadColor { at: location, text } if location == at(2, 2) && text == "00000"
+        ));
+        assert!(matches!(
+            ron("(name: \"None\", palettes: [])"),
+            PaletteError::Empty
+        ));
+    }
+
+    #[test]
+    fn bundled_palette_sets_load() {
+        for path in PaletteSets::PATHS {
+            let path = Path::new("assets").join(path);
+            let bytes = std::fs::read(&path).unwrap();
+
+            PaletteSet::from_file(&path, &bytes).unwrap();
+        }
+    }
+}
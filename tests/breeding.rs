@@ -0,0 +1,68 @@
+//! Offspring take after their parents, whatever palettes are loaded when
+//! they're bred.
+
+use greachers_survival::{
+    color::{Color, GreacherColorPalette, GreacherPalettes},
+    greachers::{
+        components::Greacher,
+        gen::{create_head_image, GREACHER_CANVAS_SIZE},
+        genome::Genome,
+        names::NameLanguage,
+        rarity::RarityWeights,
+    },
+};
+
+/// A set of palettes in shades of one gray, told apart by `value`.
+fn palette_set(name: &str, value: u8) -> GreacherPalettes {
+    let palettes = (0..4)
+        .map(|shade| {
+            GreacherColorPalette::new(
+                (0..4)
+                    .map(|slot| Color::new(value, shade, slot, 255))
+                    .collect(),
+            )
+        })
+        .collect();
+
+    GreacherPalettes::new(None, name.to_string(), palettes)
+}
+
+#[test]
+fn offspring_keep_a_parents_palette_and_genome() {
+    let language = NameLanguage::builtin();
+    let rarity_weights = RarityWeights::default();
+    let mut head = create_head_image(GREACHER_CANVAS_SIZE);
+    let mut parent = |seed, palettes: &GreacherPalettes| {
+        Greacher::from_genome(
+            Genome::from_seed(seed),
+            &mut head,
+            palettes,
+            &language,
+            &rarity_weights,
+        )
+    };
+
+    for seed in 0..50 {
+        let a = parent(seed, &palette_set("A", 10));
+        let b = parent(seed + 1000, &palette_set("B", 20));
+
+        // The parents' sets were swapped out before they were bred.
+        let child = Greacher::breed(
+            &a,
+            &b,
+            0.,
+            &mut create_head_image(GREACHER_CANVAS_SIZE),
+            &palette_set("C", 30),
+            &language,
+            &rarity_weights,
+        );
+
+        assert!(
+            child.palette == a.palette || child.palette == b.palette,
+            "Seed {} got {:?}, which neither parent has",
+            seed,
+            child.palette.0
+        );
+        assert_eq!(child.parents, Some((a.genome, b.genome)));
+    }
+}
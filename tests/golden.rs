@@ -0,0 +1,270 @@
+//! Pins what known seeds generate into with each generator version, so
+//! changes to the generators can't quietly alter greachers people already
+//! have. A change that's meant to alter them needs a new version instead.
+
+use bevy::prelude::Image;
+use greachers_survival::{
+    color::{Color, GreacherColorPalette, GreacherPalettes},
+    greachers::{
+        code::GreacherCodeError,
+        components::Greacher,
+        gen::{create_head_image, GeneratorVersion, GREACHER_CANVAS_SIZE},
+        genome::Genome,
+        names::NameLanguage,
+        rarity::RarityWeights,
+    },
+};
+
+const SEEDS: [u64; 8] = [0, 1, 2, 7, 42, 1337, 0xdead_beef, u64::MAX];
+
+/// Hash of everything a seed generates into with a version: names in both
+/// [`languages`], rarity, palette and the ramps drawn from it, size, and the
+/// head and body atlases.
+#[rustfmt::skip]
+const GOLDEN: &[(GeneratorVersion, u64, u64)] = &[
+    (GeneratorVersion::V1, 0x0, 0x7b225fb03959b1e1),
+    (GeneratorVersion::V1, 0x1, 0xa439a37bb311eafb),
+    (GeneratorVersion::V1, 0x2, 0xce9378615dd79480),
+    (GeneratorVersion::V1, 0x7, 0x4f019afc97a9013a),
+    (GeneratorVersion::V1, 0x2a, 0x9d3be2e8c5c43b62),
+    (GeneratorVersion::V1, 0x539, 0x67194ef1de327803),
+    (GeneratorVersion::V1, 0xdeadbeef, 0x47662cf67be62ccc),
+    (GeneratorVersion::V1, 0xffffffffffffffff, 0x85f24e50f998a138),
+    (GeneratorVersion::V2, 0x0, 0x7b225fb03959b1e1),
+    (GeneratorVersion::V2, 0x1, 0xa439a37bb311eafb),
+    (GeneratorVersion::V2, 0x2, 0xce9378615dd79480),
+    (GeneratorVersion::V2, 0x7, 0x4f019afc97a9013a),
+    (GeneratorVersion::V2, 0x2a, 0x9fede72c2dad7061),
+    (GeneratorVersion::V2, 0x539, 0xc6425cbad3c02131),
+    (GeneratorVersion::V2, 0xdeadbeef, 0x47662cf67be62ccc),
+    (GeneratorVersion::V2, 0xffffffffffffffff, 0x85f24e50f998a138),
+    (GeneratorVersion::V3, 0x0, 0x0a90d024e7de3e8d),
+    (GeneratorVersion::V3, 0x1, 0x4e0756c92e7727fb),
+    (GeneratorVersion::V3, 0x2, 0x39fe1914683e2960),
+    (GeneratorVersion::V3, 0x7, 0xf3f36479107c7012),
+    (GeneratorVersion::V3, 0x2a, 0x9fede72c2dad7061),
+    (GeneratorVersion::V3, 0x539, 0x5b2f4a683ed15b99),
+    (GeneratorVersion::V3, 0xdeadbeef, 0xeeae4d636a00d96c),
+    (GeneratorVersion::V3, 0xffffffffffffffff, 0xbd00239cfc732068),
+    (GeneratorVersion::V4, 0x0, 0x1522adf1489c64f7),
+    (GeneratorVersion::V4, 0x1, 0x274329f64e4eb07b),
+    (GeneratorVersion::V4, 0x2, 0xa6403865c2378760),
+    (GeneratorVersion::V4, 0x7, 0x3293a557ad7d792a),
+    (GeneratorVersion::V4, 0x2a, 0x9fede72c2dad7061),
+    (GeneratorVersion::V4, 0x539, 0x5b2f4a683ed15b99),
+    (GeneratorVersion::V4, 0xdeadbeef, 0xeeae4d636a00d96c),
+    (GeneratorVersion::V4, 0xffffffffffffffff, 0xd6715127b456a0d5),
+    (GeneratorVersion::V5, 0x0, 0x3e78134bf5973785),
+    (GeneratorVersion::V5, 0x1, 0x0786184993a54265),
+    (GeneratorVersion::V5, 0x2, 0x3055f36fbfe2c4a4),
+    (GeneratorVersion::V5, 0x7, 0xc6877ffe07d9c3fb),
+    (GeneratorVersion::V5, 0x2a, 0xd7dade7101e0b062),
+    (GeneratorVersion::V5, 0x539, 0x926da08e1c8ccb25),
+    (GeneratorVersion::V5, 0xdeadbeef, 0x599f676cedca7523),
+    (GeneratorVersion::V5, 0xffffffffffffffff, 0xd6f582c059ea8e3d),
+    (GeneratorVersion::V6, 0x0, 0x90c64471bf288b03),
+    (GeneratorVersion::V6, 0x1, 0xe5ef457a1fac7937),
+    (GeneratorVersion::V6, 0x2, 0xc031cf691f707aab),
+    (GeneratorVersion::V6, 0x7, 0x69392e64672d3cdf),
+    (GeneratorVersion::V6, 0x2a, 0xd7dade7101e0b062),
+    (GeneratorVersion::V6, 0x539, 0x6a3a79c765df64bb),
+    (GeneratorVersion::V6, 0xdeadbeef, 0x653c6b1c6532e5e1),
+    (GeneratorVersion::V6, 0xffffffffffffffff, 0xcc101fb9eefa6561),
+];
+
+/// Fixed palettes, so the table doesn't depend on the palette asset. The
+/// generated ones are added after them as usual. The shorter and longer ones
+/// are drawn from through ramps.
+fn palettes() -> GreacherPalettes {
+    let palette = |colors: &[[u8; 3]]| {
+        GreacherColorPalette::new(
+            colors
+                .iter()
+                .map(|&[r, g, b]| Color::new(r, g, b, 255))
+                .collect(),
+        )
+    };
+
+    GreacherPalettes::new(
+        None,
+        "Golden".to_string(),
+        vec![
+            palette(&[[20, 12, 28], [68, 36, 52], [133, 76, 48], [210, 125, 44]]),
+            palette(&[[48, 52, 109], [78, 74, 78], [89, 125, 206], [109, 194, 202]]),
+            palette(&[[52, 96, 36], [98, 93, 87], [109, 170, 44], [218, 212, 94]]),
+            palette(&[[40, 24, 60], [140, 90, 160], [240, 220, 250]]),
+            palette(&[
+                [16, 20, 32],
+                [40, 56, 72],
+                [64, 100, 112],
+                [96, 148, 140],
+                [160, 196, 164],
+                [232, 240, 208],
+            ]),
+        ],
+    )
+}
+
+/// Fixed languages, so the table doesn't depend on the name assets: the
+/// built-in templates, and a Markov chain.
+fn languages() -> [NameLanguage; 2] {
+    let markov = NameLanguage::from_ron(
+        br#"(
+            name: "Golden",
+            prefixes: ["old "],
+            postfixes: [" of the bog"],
+            prefix_chance: 0.1,
+            postfix_chance: 0.1,
+            markov: Some((
+                order: 2,
+                min_length: 4,
+                max_length: 8,
+                words: [
+                    "moss", "fern", "bracken", "lichen", "thistle", "bramble", "hollow", "sorrel",
+                    "burdock", "nettle", "mallow", "rowan", "alder", "willow", "hazel", "marsh",
+                    "sedge", "heather", "gorse", "tansy", "yarrow", "clover", "morel", "truffle",
+                ],
+            )),
+        )"#,
+    )
+    .unwrap();
+
+    [NameLanguage::builtin(), markov]
+}
+
+/// FNV-1a, which is stable across platforms and Rust versions unlike
+/// the standard library's hashers.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn write_color(&mut self, color: Color) {
+        self.write(&<[u8; 4]>::from(color));
+    }
+}
+
+fn generate(version: GeneratorVersion, seed: u64, language: &NameLanguage) -> (Greacher, Image) {
+    let mut head = create_head_image(GREACHER_CANVAS_SIZE);
+    let greacher = Greacher::from_versioned_genome(
+        Genome::from_seed(seed),
+        version,
+        &mut head,
+        &palettes(),
+        language,
+        &RarityWeights::default(),
+    );
+
+    (greacher, head)
+}
+
+fn golden_hash(version: GeneratorVersion, seed: u64) -> u64 {
+    let [builtin, markov] = languages();
+    let (mut greacher, head) = generate(version, seed, &builtin);
+    let (markov_greacher, _) = generate(version, seed, &markov);
+
+    let mut hash = Fnv::new();
+
+    for name in [&greacher.name, &markov_greacher.name] {
+        hash.write(name.as_bytes());
+        hash.write(&[0]);
+    }
+    hash.write(greacher.rarity.to_string().as_bytes());
+    let palette_index = palettes().find(&greacher.palette.0).unwrap();
+    hash.write(&palette_index.to_le_bytes());
+    hash.write(&greacher.size.to_le_bytes());
+
+    let palette = &greacher.palette.1;
+    for color in &palette.colors {
+        hash.write_color(*color);
+    }
+    for slots in 1..=8 {
+        for slot in 0..slots {
+            hash.write_color(palette.ramp(slot, slots));
+        }
+    }
+
+    hash.write(&head.data);
+    match greacher.generate_body() {
+        Some(body) => hash.write(&body.data),
+        None => hash.write(&[0]),
+    }
+
+    hash.0
+}
+
+#[test]
+fn known_seeds_generate_the_same_greachers() {
+    for version in GeneratorVersion::ALL {
+        assert!(
+            GOLDEN.iter().any(|(golden, _, _)| *golden == version),
+            "{:?} has no golden seeds",
+            version
+        );
+    }
+
+    for (version, seed, expected) in GOLDEN {
+        let actual = golden_hash(*version, *seed);
+
+        assert!(
+            actual == *expected,
+            "Seed {:#x} generates differently with {:?}: expected {:#018x}, got {:#018x}",
+            seed,
+            version,
+            expected,
+            actual
+        );
+    }
+}
+
+#[test]
+fn codes_keep_their_version() {
+    for version in GeneratorVersion::ALL {
+        for seed in SEEDS {
+            let (greacher, head) = generate(version, seed, &languages()[0]);
+
+            let mut rebuilt_head = create_head_image(GREACHER_CANVAS_SIZE);
+            let rebuilt = Greacher::from_code(
+                &greacher.to_code().unwrap(),
+                &mut rebuilt_head,
+                &palettes(),
+                &languages()[0],
+            )
+            .unwrap();
+
+            assert_eq!(rebuilt.version, version);
+            assert_eq!(rebuilt.name, greacher.name);
+            assert_eq!(rebuilt.rarity, greacher.rarity);
+            assert_eq!(rebuilt_head.data, head.data);
+        }
+    }
+}
+
+#[test]
+fn codes_need_their_palettes_and_language() {
+    let [builtin, markov] = languages();
+    // Greachers of V5 are always colored with loaded palettes.
+    let (greacher, _) = generate(GeneratorVersion::V5, 42, &builtin);
+    let code = greacher.to_code().unwrap();
+    let mut head = create_head_image(GREACHER_CANVAS_SIZE);
+
+    let mut other_set = palettes();
+    other_set.set = "Other".to_string();
+    assert_eq!(
+        Greacher::from_code(&code, &mut head, &other_set, &builtin).err(),
+        Some(GreacherCodeError::UnknownPaletteSet("Golden".to_string()))
+    );
+
+    assert_eq!(
+        Greacher::from_code(&code, &mut head, &palettes(), &markov).err(),
+        Some(GreacherCodeError::UnknownLanguage(builtin.name.clone()))
+    );
+}